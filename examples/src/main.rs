@@ -8,11 +8,14 @@ fn main() -> Result<(), ConfigError> {
     let config_manager = config_manager_builder
         .build()?;
     let (term_tx, term_rx) = oneshot::channel();
-    let rx = config_manager.watch_file_changes(term_rx)?;
+    let (rx, handle) = config_manager.watch_file_changes(term_rx)?;
 
     thread::spawn(move || {
         for event in rx {
-            println!("File Watcher Event: {:?}", event.unwrap())
+            match event {
+                Ok(event) => println!("File Watcher Event: {:?}", event),
+                Err(e) => println!("File Watcher Error: {}", e),
+            }
         }
     });
 
@@ -20,6 +23,7 @@ fn main() -> Result<(), ConfigError> {
     thread::sleep(Duration::from_secs(10));
     // When you want to stop watching the file
     term_tx.send(()).unwrap();
+    handle.join()?;
 
     Ok(())
 }
\ No newline at end of file
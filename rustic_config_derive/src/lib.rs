@@ -0,0 +1,488 @@
+//! # Rustic Config Derive
+//!
+//! `#[derive(Config)]` for [`rustic_config`](https://docs.rs/rustic_config) configuration
+//! structs. Use it through the `derive` feature of `rustic_config`, which documents the
+//! supported attributes on its `Config` trait.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    ext::IdentExt, parse_macro_input, punctuated::Punctuated, Attribute, Data, DeriveInput, Expr,
+    ExprLit, Fields, GenericArgument, Lit, LitStr, Meta, MetaNameValue, PathArguments, Token, Type,
+};
+
+/// Implements `rustic_config::Config` for a struct with named fields.
+#[proc_macro_derive(Config, attributes(config))]
+pub fn derive_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// The `#[config(...)]` attributes of the struct.
+#[derive(Default)]
+struct StructAttrs {
+    files: Vec<LitStr>,
+    env_prefix: Option<LitStr>,
+    strict: bool,
+    rename_all: Option<RenameRule>,
+}
+
+// The case conversions of `#[serde(rename_all = "...")]`, applied to snake_case field names as
+// serde does.
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(rule: &LitStr) -> syn::Result<Self> {
+        Ok(match rule.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return Err(syn::Error::new_spanned(rule, "unknown `rename_all` rule")),
+        })
+    }
+
+    fn apply(self, field: &str) -> String {
+        match self {
+            Self::Lower | Self::Snake => field.to_string(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            Self::Camel => {
+                let pascal = Self::Pascal.apply(field);
+                let mut chars = pascal.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                    None => pascal,
+                }
+            }
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+// The `#[config(...)]` attributes of a field, along with what is read from its other
+// attributes.
+#[derive(Default)]
+struct FieldAttrs {
+    default: Option<Expr>,
+    env: Option<LitStr>,
+    cli: Option<LitStr>,
+    doc: Option<String>,
+    secret: bool,
+    min: Option<Expr>,
+    max: Option<Expr>,
+    regex: Option<LitStr>,
+    non_empty: bool,
+    nested: bool,
+    rename: Option<String>,
+}
+
+impl StructAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("config")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("file") {
+                    parsed.files.push(meta.value()?.parse()?);
+                } else if meta.path.is_ident("env_prefix") {
+                    parsed.env_prefix = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("strict") {
+                    parsed.strict = true;
+                } else {
+                    return Err(meta.error("expected `file`, `env_prefix` or `strict`"));
+                }
+                Ok(())
+            })?;
+        }
+        for meta in serde_metas(attrs)? {
+            if !meta.path().is_ident("rename_all") {
+                continue;
+            }
+            match &meta {
+                Meta::NameValue(nv) => parsed.rename_all = Some(RenameRule::parse(&lit_str(nv)?)?),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        meta,
+                        "`Config` only supports `#[serde(rename_all = \"...\")]`",
+                    ))
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        let mut doc = Vec::new();
+        for attr in attrs {
+            if attr.path().is_ident("doc") {
+                if let Meta::NameValue(nv) = &attr.meta {
+                    if let Expr::Lit(ExprLit {
+                        lit: Lit::Str(line),
+                        ..
+                    }) = &nv.value
+                    {
+                        doc.push(line.value().trim().to_string());
+                    }
+                }
+            } else if attr.path().is_ident("config") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("default") {
+                        parsed.default = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("env") {
+                        parsed.env = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("cli") {
+                        parsed.cli = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("doc") {
+                        parsed.doc = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else if meta.path.is_ident("secret") {
+                        parsed.secret = true;
+                    } else if meta.path.is_ident("regex") {
+                        let pattern: LitStr = meta.value()?.parse()?;
+                        if let Err(e) = regex::Regex::new(&pattern.value()) {
+                            return Err(syn::Error::new(
+                                pattern.span(),
+                                format!("invalid regex: {}", e),
+                            ));
+                        }
+                        parsed.regex = Some(pattern);
+                    } else if meta.path.is_ident("non_empty") {
+                        parsed.non_empty = true;
+                    } else if meta.path.is_ident("nested") {
+                        parsed.nested = true;
+                    } else if meta.path.is_ident("range") {
+                        meta.parse_nested_meta(|bound| {
+                            if bound.path.is_ident("min") {
+                                parsed.min = Some(bound.value()?.parse()?);
+                            } else if bound.path.is_ident("max") {
+                                parsed.max = Some(bound.value()?.parse()?);
+                            } else {
+                                return Err(bound.error("expected `min` or `max`"));
+                            }
+                            Ok(())
+                        })?;
+                    } else {
+                        return Err(meta.error(
+                            "expected one of `default`, `env`, `cli`, `doc`, `secret`, `range`, \
+                             `regex`, `non_empty` or `nested`",
+                        ));
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        for meta in serde_metas(attrs)? {
+            let path = meta.path();
+            if path.is_ident("rename") {
+                match &meta {
+                    Meta::NameValue(nv) => parsed.rename = Some(lit_str(nv)?.value()),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            meta,
+                            "`Config` only supports `#[serde(rename = \"...\")]`",
+                        ))
+                    }
+                }
+            } else if path.is_ident("alias") {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "`Config` doesn't support `#[serde(alias)]`, use `#[config(env)]` or \
+                     `#[config(cli)]` for other names",
+                ));
+            } else if path.is_ident("flatten") {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "`Config` doesn't support `#[serde(flatten)]`, use a `#[config(nested)]` \
+                     field instead",
+                ));
+            }
+        }
+        if parsed.doc.is_none() && !doc.is_empty() {
+            parsed.doc = Some(doc.join("\n").trim().to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+// Returns the items of the `#[serde(...)]` attributes in `attrs`.
+fn serde_metas(attrs: &[Attribute]) -> syn::Result<Vec<Meta>> {
+    let mut metas = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        metas.extend(attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?);
+    }
+    Ok(metas)
+}
+
+// Returns the string literal of `name = "..."`.
+fn lit_str(nv: &MetaNameValue) -> syn::Result<LitStr> {
+    match &nv.value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(value),
+            ..
+        }) => Ok(value.clone()),
+        value => Err(syn::Error::new_spanned(value, "expected a string literal")),
+    }
+}
+
+// Whether `ty` is written as `bool` or `Option<bool>`, which flags set without a value.
+fn is_bool(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    if segment.ident == "bool" {
+        return true;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if segment.ident == "Option" => {
+            matches!(args.args.first(), Some(GenericArgument::Type(inner)) if is_bool(inner))
+        }
+        _ => false,
+    }
+}
+
+// Whether `ty` is written as an `Option`.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "`Config` can't be derived for generic structs",
+        ));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "`Config` can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "`Config` can only be derived for structs with named fields",
+        ));
+    };
+
+    let StructAttrs {
+        files,
+        env_prefix,
+        strict,
+        rename_all,
+    } = StructAttrs::parse(&input.attrs)?;
+
+    let private = quote!(::rustic_config::typed::__private);
+    let mut configure = Vec::new();
+    let mut describe = Vec::new();
+    let mut check = Vec::new();
+
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let key = match (&attrs.rename, rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply(&ident.unraw().to_string()),
+            (None, None) => ident.unraw().to_string(),
+        };
+        let key = quote!(#private::key(prefix, #key));
+
+        if attrs.nested {
+            configure.push(quote! {
+                <#ty as ::rustic_config::typed::Config>::configure(builder, &#key);
+            });
+            describe.push(quote! {
+                fields.extend(<#ty as ::rustic_config::typed::Config>::fields(&#key));
+            });
+            check.push(quote! {
+                ::rustic_config::typed::Config::check(&self.#ident, &#key, violations);
+            });
+            continue;
+        }
+
+        configure.push(quote!(builder.known_keys([#key]);));
+        if let Some(default) = &attrs.default {
+            let message = format!("the default of `{}` must serialize", ident);
+            configure.push(quote! {
+                builder.set_default(&#key, #private::to_value(#default).expect(#message));
+            });
+        }
+        if let Some(env) = &attrs.env {
+            configure.push(quote!(builder.env_alias(#env, &#key);));
+        }
+        if let Some(cli) = &attrs.cli {
+            configure.push(quote!(builder.cli_alias(#cli, #key);));
+        }
+        if !is_bool(ty) {
+            configure.push(quote!(builder.cli_value_flag(#key);));
+        }
+
+        let option = |value: Option<TokenStream2>| match value {
+            Some(value) => quote!(::std::option::Option::Some(#value)),
+            None => quote!(::std::option::Option::None),
+        };
+        let doc = option(attrs.doc.as_ref().map(|doc| quote!(#doc.to_string())));
+        let env = option(attrs.env.as_ref().map(|env| quote!(#env.to_string())));
+        let cli = option(attrs.cli.as_ref().map(|cli| quote!(#cli.to_string())));
+        let default = match &attrs.default {
+            Some(default) => quote!(#private::to_value(#default).ok()),
+            None => quote!(::std::option::Option::None),
+        };
+        let secret = attrs.secret;
+        describe.push(quote! {
+            fields.push(::rustic_config::typed::ConfigField {
+                key: #key,
+                doc: #doc,
+                env: #env,
+                cli: #cli,
+                default: #default,
+                secret: #secret,
+            });
+        });
+
+        let mut checks = Vec::new();
+        if attrs.min.is_some() || attrs.max.is_some() {
+            let min = option(attrs.min.as_ref().map(ToTokens::to_token_stream));
+            let max = option(attrs.max.as_ref().map(ToTokens::to_token_stream));
+            checks.push(quote!(#private::check_range(value, #min, #max, #secret)));
+        }
+        if let Some(regex) = &attrs.regex {
+            checks.push(quote! {{
+                static REGEX: ::std::sync::OnceLock<#private::Regex> = ::std::sync::OnceLock::new();
+                #private::check_regex(value, &REGEX, #regex, #secret)
+            }});
+        }
+        let mut field_checks = Vec::new();
+        if attrs.non_empty {
+            field_checks.push(quote! {
+                if let ::std::option::Option::Some(message) = #private::check_non_empty(&self.#ident) {
+                    violations.push(::std::format!("{}: {}", key, message));
+                }
+            });
+        }
+        if !checks.is_empty() {
+            let value_checks = quote! {
+                #(
+                    if let ::std::option::Option::Some(message) = #checks {
+                        violations.push(::std::format!("{}: {}", key, message));
+                    }
+                )*
+            };
+            field_checks.push(if is_option(ty) {
+                quote! {
+                    if let ::std::option::Option::Some(value) = &self.#ident {
+                        #value_checks
+                    }
+                }
+            } else {
+                quote! {
+                    let value = &self.#ident;
+                    #value_checks
+                }
+            });
+        }
+        if !field_checks.is_empty() {
+            check.push(quote! {
+                {
+                    let key = #key;
+                    #(#field_checks)*
+                }
+            });
+        }
+    }
+
+    // Without a prefix, only the variables named by `env` attributes are read, rather than the
+    // whole environment.
+    let env = match env_prefix {
+        Some(prefix) => quote!(builder.env_prefix(#prefix);),
+        None => quote!(builder.env_aliases_only();),
+    };
+
+    Ok(quote! {
+        impl ::rustic_config::typed::Config for #name {
+            #[allow(unused_variables)]
+            fn configure(builder: &mut ::rustic_config::ConfigManagerBuilder, prefix: &str) {
+                #(#configure)*
+            }
+
+            #[allow(unused_variables)]
+            fn fields(prefix: &str) -> ::std::vec::Vec<::rustic_config::typed::ConfigField> {
+                let mut fields = ::std::vec::Vec::new();
+                #(#describe)*
+                fields
+            }
+
+            #[allow(unused_variables)]
+            fn check(&self, prefix: &str, violations: &mut ::std::vec::Vec<::std::string::String>) {
+                #(#check)*
+            }
+
+            fn builder() -> ::rustic_config::ConfigManagerBuilder {
+                let mut builder = ::rustic_config::ConfigManagerBuilder::new();
+                #(
+                    builder.add_source(::rustic_config::ConfigSource::File(
+                        ::rustic_config::FilePath::new(#files),
+                    ));
+                )*
+                #env
+                builder
+                    .add_source(::rustic_config::ConfigSource::Environment)
+                    .add_source(::rustic_config::ConfigSource::CommandLine(
+                        ::std::env::args().collect(),
+                    ))
+                    .strict(#strict)
+                    .add_validator(|cm| {
+                        <Self as ::rustic_config::typed::Config>::from_manager(cm).map(|_| ())
+                    });
+                <Self as ::rustic_config::typed::Config>::configure(&mut builder, "");
+                builder
+            }
+        }
+    })
+}
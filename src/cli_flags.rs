@@ -0,0 +1,118 @@
+use serde_json::Value;
+
+use crate::{coerce_value, error::ConfigError, insert_path, ConfigMap};
+
+/// Reads configuration values from command-line flags.
+///
+/// Supported forms:
+///
+/// * `--key=value`, and `--key value` for keys registered with
+///   [`CliFlagsReader::with_value_flag`]
+/// * `--flag` sets `true`, `--no-flag` sets `false`
+/// * repeated flags (`--tag a --tag b`) collect into an array
+/// * dotted keys (`--database.pool.size=10`) produce nested objects
+///
+/// Arguments that don't start with `--` (such as the program name or positional arguments)
+/// are ignored, and a bare `--` stops parsing. Values are coerced into booleans and numbers
+/// where possible.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::cli_flags::CliFlagsReader;
+///
+/// let args = vec!["myapp".to_string(), "--database.pool.size=10".to_string(), "--verbose".to_string()];
+/// let cfg = CliFlagsReader::new().read(&args).unwrap();
+/// assert_eq!(cfg["database"]["pool"]["size"], 10);
+/// assert_eq!(cfg["verbose"], true);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliFlagsReader {
+    // Keys whose value may be passed as the next argument.
+    value_flags: Vec<String>,
+}
+
+impl CliFlagsReader {
+    /// Creates a reader for which only `--key=value` sets values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let `--key value` set `key` from the next argument, which is otherwise taken as a
+    /// positional argument.
+    pub fn with_value_flag<S: AsRef<str>>(mut self, key: S) -> Self {
+        let key = key.as_ref().to_string();
+        if !self.value_flags.contains(&key) {
+            self.value_flags.push(key);
+        }
+        self
+    }
+
+    /// Reads the flags in `args` into a configuration map.
+    ///
+    /// `--key=value` sets `key`, and so does `--key value` if `key` was registered with
+    /// [`CliFlagsReader::with_value_flag`]. Any other `--flag` sets `true` and `--no-flag` sets
+    /// `false`. Dotted keys such as `--database.url` set nested values, and a flag given several
+    /// times collects its values into an array, in order. Values are coerced into booleans and
+    /// numbers where possible, so `--port=80` sets a number while `--port=01` keeps the string.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::ParseError`] if a registered value flag is last or followed by
+    /// another flag, as in `--port --verbose`, or if a key is empty, as in `--=1` or `--a..b`.
+    pub fn read(&self, args: &[String]) -> Result<ConfigMap, ConfigError> {
+        // Keep flags in the order they first appear, collecting repeated ones.
+        let mut flags: Vec<(String, Vec<Value>)> = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                break;
+            }
+            let Some(flag) = arg.strip_prefix("--") else {
+                continue;
+            };
+
+            let (key, value) = match flag.split_once('=') {
+                Some((key, raw)) => (key, coerce_value(raw)),
+                None if self.value_flags.iter().any(|k| k == flag) => match args.next() {
+                    Some(raw) if !raw.starts_with("--") => (flag, coerce_value(raw)),
+                    _ => {
+                        return Err(ConfigError::ParseError(format!(
+                            "Missing value for command-line flag: {}",
+                            arg
+                        )))
+                    }
+                },
+                None => match flag.strip_prefix("no-") {
+                    Some(negated) if !negated.is_empty() => (negated, Value::Bool(false)),
+                    _ => (flag, Value::Bool(true)),
+                },
+            };
+
+            if key.is_empty() || key.split('.').any(str::is_empty) {
+                return Err(ConfigError::ParseError(format!(
+                    "Invalid command-line flag: {}",
+                    arg
+                )));
+            }
+
+            match flags.iter_mut().find(|(k, _)| k == key) {
+                Some((_, values)) => values.push(value),
+                None => flags.push((key.to_string(), vec![value])),
+            }
+        }
+
+        let mut cfg_map = ConfigMap::new();
+        for (key, mut values) in flags {
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                Value::Array(values)
+            };
+            let path = key.split('.').map(String::from).collect::<Vec<_>>();
+            insert_path(&mut cfg_map, &path, value);
+        }
+        Ok(cfg_map)
+    }
+}
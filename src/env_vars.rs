@@ -0,0 +1,133 @@
+use std::env;
+
+use crate::{coerce_value, error::ConfigError, insert_path, ConfigMap};
+
+/// Reads configuration values from the process environment.
+///
+/// Only variables starting with the configured prefix are considered, and without a prefix only
+/// the variables given to [`EnvVarsReader::with_alias`] are read. The remainder of the
+/// variable name is lowercased and split on the separator to build nested keys, so with the
+/// prefix `MYAPP_` and the default separator `__`, `MYAPP_DATABASE__URL` becomes `database.url`.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::env_vars::EnvVarsReader;
+///
+/// let reader = EnvVarsReader::new().with_prefix("MYAPP_");
+/// let cfg = reader
+///     .read_from(vec![("MYAPP_DATABASE__POOL_SIZE".to_string(), "10".to_string())])
+///     .unwrap();
+/// assert_eq!(cfg["database"]["pool_size"], 10);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVarsReader {
+    prefix: String,
+    separator: String,
+    // Variables read under an explicit key, regardless of the prefix.
+    aliases: Vec<(String, Vec<String>)>,
+    // Whether only the aliased variables are read.
+    aliases_only: bool,
+}
+
+impl Default for EnvVarsReader {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            separator: "__".to_string(),
+            aliases: Vec::new(),
+            aliases_only: false,
+        }
+    }
+}
+
+impl EnvVarsReader {
+    /// Creates a reader with no prefix and `__` as the nesting separator.
+    ///
+    /// Until a prefix is set, only aliased variables are read rather than the whole environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only read variables starting with `prefix`, which is stripped from the key.
+    pub fn with_prefix<S: AsRef<str>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.as_ref().to_string();
+        self
+    }
+
+    /// Split variable names on `separator` to produce nested keys.
+    pub fn with_separator<S: AsRef<str>>(mut self, separator: S) -> Self {
+        self.separator = separator.as_ref().to_string();
+        self
+    }
+
+    /// Read the variable `var` into the dotted `key`, such as `database.url`, whether or not
+    /// it starts with the prefix.
+    pub fn with_alias<S: AsRef<str>, K: AsRef<str>>(mut self, var: S, key: K) -> Self {
+        let var = var.as_ref().to_string();
+        let path = key.as_ref().split('.').map(str::to_string).collect();
+        self.aliases.retain(|(existing, _)| *existing != var);
+        self.aliases.push((var, path));
+        self
+    }
+
+    /// Only read the variables given to [`EnvVarsReader::with_alias`], ignoring the prefix.
+    pub fn with_aliases_only(mut self) -> Self {
+        self.aliases_only = true;
+        self
+    }
+
+    /// Reads the matching variables of the current process environment.
+    ///
+    /// Variables whose name or value is not valid unicode are skipped.
+    pub fn read(&self) -> Result<ConfigMap, ConfigError> {
+        self.read_from(
+            env::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?))),
+        )
+    }
+
+    /// Reads the matching variables from the given `(name, value)` pairs.
+    pub fn read_from<I>(&self, vars: I) -> Result<ConfigMap, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        if self.separator.is_empty() {
+            return Err(ConfigError::ParseError(
+                "Environment variable separator must not be empty".to_string(),
+            ));
+        }
+
+        // Sort so that conflicting keys (`DB` vs `DB__URL`) resolve the same way on every run.
+        let mut vars = vars
+            .into_iter()
+            .filter_map(|(k, v)| Some((self.key_path(&k)?, v)))
+            .collect::<Vec<_>>();
+        vars.sort();
+
+        let mut cfg_map = ConfigMap::new();
+        for (path, raw) in vars {
+            insert_path(&mut cfg_map, &path, coerce_value(&raw));
+        }
+        Ok(cfg_map)
+    }
+
+    // Maps a variable name to its nested key path, or `None` if it doesn't belong to this source.
+    fn key_path(&self, name: &str) -> Option<Vec<String>> {
+        if let Some((_, path)) = self.aliases.iter().find(|(var, _)| var == name) {
+            return Some(path.clone());
+        }
+        if self.aliases_only || self.prefix.is_empty() {
+            return None;
+        }
+        let stripped = name.strip_prefix(&self.prefix)?;
+        let path = stripped
+            .split(&self.separator)
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if path.iter().any(String::is_empty) {
+            return None;
+        }
+        Some(path)
+    }
+}
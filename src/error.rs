@@ -4,7 +4,7 @@ use crate::{FilePath, FileType};
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum ConfigError {
-    #[error("Feature not passed {:?}", 0)]
+    #[error("Feature not passed {0:?}")]
     FeatureNotSupported(FileType),
 
     #[error("Must pass sources to read from")]
@@ -30,4 +30,13 @@ pub enum ConfigError {
 
     #[error("Failed to watch configuration file: {0}")]
     FileWatchError(String),
+
+    #[error("Failed to interpolate configuration: {0}")]
+    InterpolationError(String),
+
+    #[error("Failed to include configuration file: {0}")]
+    IncludeError(String),
+
+    #[error("Configuration build was cancelled: {0}")]
+    BuildCancelled(String),
 }
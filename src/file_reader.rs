@@ -1,17 +1,76 @@
-use crate::{error::ConfigError, ConfigMap, FilePath};
+#[cfg(feature = "toml")]
+use serde_json::{Map, Number, Value};
+
+use std::collections::HashMap;
+
+#[cfg(feature = "toml")]
+use crate::provenance::locate_toml;
+use crate::provenance::{locate_json, locate_yaml, Location};
+use crate::{error::ConfigError, ConfigMap, FilePath, FileType};
 
 pub trait Reader {
+    /// Reads and parses the configuration file at `path`.
     fn read(&self, path: &str) -> Result<ConfigMap, ConfigError>;
+
+    /// Parses the content of a configuration file, for readers that don't need the file itself.
+    /// The default returns [`ConfigError::ParseError`].
+    fn parse(&self, _content: &str) -> Result<ConfigMap, ConfigError> {
+        Err(ConfigError::ParseError(
+            "This reader can only read from a path".to_string(),
+        ))
+    }
+
+    /// Returns the location of each key in `content`, keyed by its path as a JSON Pointer, e.g.
+    /// `/database/url`. Used for provenance; readers that can't locate keys return an empty map.
+    fn locate(&self, _content: &str) -> HashMap<String, Location> {
+        HashMap::new()
+    }
+}
+
+// Parses the content of the file at `path` with the reader for its type, along with the
+// location of its keys.
+pub(crate) fn parse_file(
+    path: &FilePath,
+    content: &str,
+) -> Result<(ConfigMap, HashMap<String, Location>), ConfigError> {
+    let file_type = path
+        .extension_type()
+        .or_else(|| FileType::sniff(content))
+        .unwrap_or_else(|| FileType::Unsupported(path.clone()));
+    let reader: Box<dyn Reader> = match file_type {
+        FileType::Json => Box::new(JsonConfigReader),
+        FileType::Yaml => Box::new(YamlConfigReader),
+        #[cfg(feature = "toml")]
+        FileType::Toml => Box::new(TomlConfigReader),
+        #[cfg(not(feature = "toml"))]
+        FileType::Toml => return Err(ConfigError::FeatureNotSupported(FileType::Toml)),
+        FileType::Unsupported(path) => {
+            return Err(ConfigError::FileReadError(path, "Unsupported".to_string()))
+        }
+    };
+    Ok((reader.parse(content)?, reader.locate(content)))
+}
+
+// Reads the file at `path` for a reader that parses its content.
+fn read_content(reader: &impl Reader, path: &str) -> Result<ConfigMap, ConfigError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::FileReadError(FilePath::new(path), e.to_string()))?;
+    reader.parse(&content)
 }
 
 pub struct YamlConfigReader;
 
 impl Reader for YamlConfigReader {
     fn read(&self, path: &str) -> Result<ConfigMap, ConfigError> {
-        let file = std::fs::File::open(path)
-            .map_err(|e| ConfigError::FileReadError(FilePath::new(path), e.to_string()))?;
+        read_content(self, path)
+    }
 
-        serde_yaml::from_reader(&file).map_err(|e| ConfigError::ParseError(e.to_string()))
+    fn parse(&self, content: &str) -> Result<ConfigMap, ConfigError> {
+        serde_yaml::from_str(content).map_err(|e| ConfigError::ParseError(e.to_string()))
+    }
+
+    fn locate(&self, content: &str) -> HashMap<String, Location> {
+        locate_yaml(content)
     }
 }
 
@@ -19,9 +78,56 @@ pub struct JsonConfigReader;
 
 impl Reader for JsonConfigReader {
     fn read(&self, path: &str) -> Result<ConfigMap, ConfigError> {
-        let file = std::fs::File::open(path)
-            .map_err(|e| ConfigError::FileReadError(FilePath::new(path), e.to_string()))?;
+        read_content(self, path)
+    }
+
+    fn parse(&self, content: &str) -> Result<ConfigMap, ConfigError> {
+        serde_json::from_str(content).map_err(|e| ConfigError::ParseError(e.to_string()))
+    }
+
+    fn locate(&self, content: &str) -> HashMap<String, Location> {
+        locate_json(content)
+    }
+}
+
+#[cfg(feature = "toml")]
+pub struct TomlConfigReader;
+
+#[cfg(feature = "toml")]
+impl Reader for TomlConfigReader {
+    fn read(&self, path: &str) -> Result<ConfigMap, ConfigError> {
+        read_content(self, path)
+    }
+
+    fn parse(&self, content: &str) -> Result<ConfigMap, ConfigError> {
+        let table: toml::Table =
+            toml::from_str(content).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        Ok(table
+            .into_iter()
+            .map(|(k, v)| (k, toml_to_json(v)))
+            .collect())
+    }
+
+    fn locate(&self, content: &str) -> HashMap<String, Location> {
+        locate_toml(content)
+    }
+}
 
-        serde_json::from_reader(&file).map_err(|e| ConfigError::ParseError(e.to_string()))
+// TOML datetimes have no JSON counterpart, so they are kept as their RFC 3339 string.
+#[cfg(feature = "toml")]
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Number(i.into()),
+        toml::Value::Float(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(arr) => Value::Array(arr.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect::<Map<String, Value>>(),
+        ),
     }
 }
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    error::ConfigError, file_reader::parse_file, provenance::Location, ConfigMap, ConfigSource,
+    FilePath,
+};
+
+// Reserved top-level key naming the files a file is composed of.
+const INCLUDE_KEY: &str = "$include";
+// Reserved top-level key naming the file a file builds upon.
+const EXTENDS_KEY: &str = "$extends";
+
+// The values a single source contributes to the configuration.
+pub(crate) struct Layer {
+    pub(crate) source: ConfigSource,
+    pub(crate) cfg_map: ConfigMap,
+    pub(crate) locations: HashMap<String, Location>,
+}
+
+// Reads the file at `path` along with, recursively, the files it extends and includes.
+//
+// The layers are returned in the order they are merged: the `$extends` base, then each
+// `$include` in order, then the file itself, so a file overrides whatever it builds upon.
+pub(crate) fn read_with_includes(path: &FilePath) -> Result<Vec<Layer>, ConfigError> {
+    let mut layers = Vec::new();
+    collect(path, &mut Vec::new(), &mut layers)?;
+    Ok(layers)
+}
+
+fn collect(
+    path: &FilePath,
+    chain: &mut Vec<(PathBuf, FilePath)>,
+    layers: &mut Vec<Layer>,
+) -> Result<(), ConfigError> {
+    let id = std::fs::canonicalize(path.as_ref()).unwrap_or_else(|_| PathBuf::from(path.as_ref()));
+    if let Some(start) = chain.iter().position(|(existing, _)| *existing == id) {
+        let cycle = chain[start..]
+            .iter()
+            .map(|(_, fp)| fp.to_string())
+            .chain([path.to_string()])
+            .collect::<Vec<_>>();
+        return Err(ConfigError::IncludeError(format!(
+            "Include cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    let content = std::fs::read_to_string(path.as_ref())
+        .map_err(|e| ConfigError::FileReadError(path.clone(), e.to_string()))?;
+    let (mut cfg_map, locations) = parse_file(path, &content)?;
+    let mut includes = Vec::new();
+    if let Some(base) = cfg_map.remove(EXTENDS_KEY) {
+        let pattern = base.as_str().ok_or_else(|| invalid(path, EXTENDS_KEY))?;
+        includes.extend(resolve(path, pattern)?);
+    }
+    match cfg_map.remove(INCLUDE_KEY) {
+        None => {}
+        Some(Value::String(pattern)) => includes.extend(resolve(path, &pattern)?),
+        Some(Value::Array(patterns)) => {
+            for pattern in patterns {
+                let pattern = pattern.as_str().ok_or_else(|| invalid(path, INCLUDE_KEY))?;
+                includes.extend(resolve(path, pattern)?);
+            }
+        }
+        Some(_) => return Err(invalid(path, INCLUDE_KEY)),
+    }
+
+    chain.push((id, path.clone()));
+    for include in &includes {
+        collect(include, chain, layers)?;
+    }
+    chain.pop();
+
+    layers.push(Layer {
+        source: ConfigSource::File(path.clone()),
+        cfg_map,
+        locations,
+    });
+    Ok(())
+}
+
+// Resolves a pattern relative to the directory of the including file. Patterns containing glob
+// metacharacters expand to the matching files, sorted by path; other patterns name a single
+// file, which must exist.
+fn resolve(including: &FilePath, pattern: &str) -> Result<Vec<FilePath>, ConfigError> {
+    let dir = Path::new(including.as_ref())
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let joined = dir.join(pattern);
+    let joined = joined.to_string_lossy();
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![FilePath::new(joined)]);
+    }
+
+    let mut matches = glob::glob(&joined)
+        .map_err(|e| {
+            ConfigError::IncludeError(format!(
+                "Invalid pattern {} in {}: {}",
+                pattern, including, e
+            ))
+        })?
+        .map(|entry| {
+            entry
+                .map(|p| FilePath::new(p.to_string_lossy()))
+                .map_err(|e| ConfigError::IncludeError(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    matches.sort();
+    Ok(matches)
+}
+
+fn invalid(path: &FilePath, key: &str) -> ConfigError {
+    ConfigError::IncludeError(format!(
+        "{} in {} must be a file name or a list of file names",
+        key, path
+    ))
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::{coerce_value, error::ConfigError, path::KeyPath, ConfigMap};
+
+// A piece of a string value: literal text or a `${...}` reference.
+enum Token {
+    Literal(String),
+    Reference {
+        name: String,
+        default: Option<String>,
+    },
+}
+
+// What a reference resolved to. Text from the environment or a default is kept verbatim when
+// embedded in a string, and coerced like environment sources when it makes up the whole value.
+enum Resolved {
+    Text(String),
+    Value(Value),
+}
+
+// Expands the references in the string values of the merged configuration.
+struct Resolver<'a, E, L> {
+    cfg_map: &'a ConfigMap,
+    env: E,
+    literal: L,
+    resolved: HashMap<String, Value>,
+    // The paths being resolved, outermost first, to detect and report reference cycles.
+    chain: Vec<String>,
+}
+
+// Returns `cfg_map` with every `${...}` reference in its string values expanded:
+//
+// * `${other.key}` is replaced by the value at `other.key`, which may itself contain references
+// * `${ENV:NAME}` and `${env.NAME}` are replaced by the environment variable `NAME`
+// * `${...:-default}` falls back to `default` if the key or variable doesn't exist
+// * `$${` is an escaped, literal `${`
+//
+// A string consisting of a single reference takes the referenced value, keeping its type.
+// Strings at paths for which `literal` returns `true` are left as they are.
+pub(crate) fn interpolate<E, L>(
+    cfg_map: &ConfigMap,
+    env: E,
+    literal: L,
+) -> Result<ConfigMap, ConfigError>
+where
+    E: Fn(&str) -> Option<String>,
+    L: Fn(&str) -> bool,
+{
+    let mut resolver = Resolver {
+        cfg_map,
+        env,
+        literal,
+        resolved: HashMap::new(),
+        chain: Vec::new(),
+    };
+    // Resolve in key order, so that errors are the same on every run.
+    let mut keys = cfg_map.keys().collect::<Vec<_>>();
+    keys.sort();
+    keys.into_iter()
+        .map(|k| Ok((k.clone(), resolver.resolve(k.clone(), &cfg_map[k])?)))
+        .collect()
+}
+
+impl<'a, E, L> Resolver<'a, E, L>
+where
+    E: Fn(&str) -> Option<String>,
+    L: Fn(&str) -> bool,
+{
+    // Returns `value`, found at `path`, with the references inside it expanded.
+    fn resolve(&mut self, path: String, value: &Value) -> Result<Value, ConfigError> {
+        if let Some(done) = self.resolved.get(&path) {
+            return Ok(done.clone());
+        }
+        if let Some(start) = self.chain.iter().position(|p| *p == path) {
+            let mut cycle = self.chain[start..].to_vec();
+            cycle.push(path);
+            return Err(ConfigError::InterpolationError(format!(
+                "Reference cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        self.chain.push(path.clone());
+        let result = match value {
+            Value::String(s) if (self.literal)(&path) => Ok(Value::String(s.clone())),
+            Value::String(s) => self.expand(s),
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| self.resolve(format!("{}[{}]", path, i), v))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), self.resolve(format!("{}.{}", path, k), v)?)))
+                .collect::<Result<Map<_, _>, _>>()
+                .map(Value::Object),
+            other => Ok(other.clone()),
+        };
+        self.chain.pop();
+
+        let value = result?;
+        self.resolved.insert(path, value.clone());
+        Ok(value)
+    }
+
+    fn expand(&mut self, s: &str) -> Result<Value, ConfigError> {
+        let tokens = self.tokenize(s)?;
+        if let [Token::Reference { name, default }] = tokens.as_slice() {
+            return Ok(match self.lookup(name, default.as_deref())? {
+                Resolved::Text(text) => coerce_value(&text),
+                Resolved::Value(value) => value,
+            });
+        }
+
+        let mut expanded = String::new();
+        for token in tokens {
+            match token {
+                Token::Literal(text) => expanded.push_str(&text),
+                Token::Reference { name, default } => {
+                    match self.lookup(&name, default.as_deref())? {
+                        Resolved::Text(text) | Resolved::Value(Value::String(text)) => {
+                            expanded.push_str(&text)
+                        }
+                        Resolved::Value(value @ (Value::Number(_) | Value::Bool(_))) => {
+                            expanded.push_str(&value.to_string())
+                        }
+                        Resolved::Value(_) => {
+                            return Err(self.error(format!(
+                                "`{}` is not a string, number or boolean and can't be embedded",
+                                name
+                            )))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Value::String(expanded))
+    }
+
+    fn lookup(&mut self, name: &str, default: Option<&str>) -> Result<Resolved, ConfigError> {
+        let found = match name.strip_prefix("ENV:").or(name.strip_prefix("env.")) {
+            Some(var) => (self.env)(var).map(Resolved::Text),
+            None => match self.find(name)? {
+                Some((path, value)) => Some(Resolved::Value(self.resolve(path, value)?)),
+                None => None,
+            },
+        };
+        match (found, default) {
+            (Some(resolved), _) => Ok(resolved),
+            (None, Some(default)) => Ok(Resolved::Text(default.to_string())),
+            (None, None) => Err(self.error(format!("`{}` is not defined", name))),
+        }
+    }
+
+    // Finds the value a reference points to, along with its canonical path.
+    fn find(&self, name: &str) -> Result<Option<(String, &'a Value)>, ConfigError> {
+        if let Some(value) = self.cfg_map.get(name) {
+            return Ok(Some((name.to_string(), value)));
+        }
+        let path = KeyPath::parse(name)
+            .map_err(|_| self.error(format!("`{}` is not a valid key path", name)))?;
+        Ok(path
+            .get(self.cfg_map)
+            .map(|value| (path.normalized(self.cfg_map).to_string(), value)))
+    }
+
+    fn tokenize(&self, s: &str) -> Result<Vec<Token>, ConfigError> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                literal.push_str(&rest[..start - 1]);
+                literal.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            literal.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| self.error(format!("Unclosed reference in `{}`", s)))?;
+            let inner = &rest[start + 2..start + end];
+            let (name, default) = match inner.split_once(":-") {
+                Some((name, default)) => (name, Some(default.to_string())),
+                None => (inner, None),
+            };
+            if name.trim().is_empty() {
+                return Err(self.error(format!("Empty reference in `{}`", s)));
+            }
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(std::mem::take(&mut literal)));
+            }
+            tokens.push(Token::Reference {
+                name: name.trim().to_string(),
+                default,
+            });
+            rest = &rest[start + end + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() || tokens.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        Ok(tokens)
+    }
+
+    // Builds an error naming the value being resolved.
+    fn error(&self, message: String) -> ConfigError {
+        match self.chain.last() {
+            Some(path) => ConfigError::InterpolationError(format!("{} (in `{}`)", message, path)),
+            None => ConfigError::InterpolationError(message),
+        }
+    }
+}
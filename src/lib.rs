@@ -4,11 +4,23 @@
 //!
 //! ## Features
 //!
-//! - Load configurations from JSON and YAML files.
+//! - Load configurations from JSON, YAML and TOML (with the `toml` feature) files, detected by
+//!   extension or, for extension-less files, by content.
 //! - Read configuration values from environment variables.
 //! - Override configurations via command-line arguments.
 //! - Support for custom data types through Serde.
-//! - Easy to use API for accessing configuration values.
+//! - Easy to use API for accessing configuration values, including nested ones by path.
+//! - Opt-in `${other.key}` and `${ENV:NAME}` references inside string values.
+//! - Composition of files with `$extends` and `$include` directives.
+//! - Profiles such as `dev` or `prod`, overlaid from `profiles` sections and sibling files.
+//! - Validation against a JSON Schema, reporting every violation with its source (with the
+//!   `schema` feature), and generation of the schema of a config struct (with the `schemars`
+//!   feature).
+//! - Async loading and a `Stream` of reload events for tokio applications (with the `async`
+//!   feature).
+//! - Typed configuration structs with `#[derive(Config)]`, declaring the defaults, sources
+//!   and constraints of each field (with the `derive` feature).
+//! - A strict mode rejecting unknown keys, with suggestions for likely typos.
 //!
 //! ## Usage
 //!
@@ -38,10 +50,21 @@
 //! - `file_reader`: Provides functionality to read configurations from various sources.
 //! - `env_vars`: Provides functionality to parse configurations from environment variables.
 //! - `cli_flags`: Provides functionality to parse configurations from cli flags.
+//! - `merge`: Provides functionality to deep merge configurations from several sources.
+//! - `path`: Provides dotted-path and JSON Pointer lookup of nested values.
+//! - `provenance`: Tracks which source supplied each configuration value.
+//! - `schema`: Provides JSON Schema validation and generation (`schema` and `schemars` features).
+//! - `shared`: Provides a thread-safe, reloadable handle to a configuration.
+//! - `typed`: Provides the `Config` trait behind `#[derive(Config)]` (`derive` feature).
+//! - `watch`: Provides file change notifications for file sources (`watch` feature).
 //! - `error`: Defines error types used throughout the library.
 
+#[cfg(feature = "derive")]
+pub use rustic_config_derive::Config;
+#[cfg(feature = "schemars")]
+pub use schemars::JsonSchema;
 pub use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Number, Value};
 use std::{
     collections::HashMap,
     fmt::{self, Display},
@@ -53,15 +76,40 @@ pub mod cli_flags;
 pub mod env_vars;
 pub mod error;
 pub mod file_reader;
+mod include;
+mod interpolate;
 pub mod manager;
+pub mod merge;
+pub mod path;
+mod profile;
+pub mod provenance;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod shared;
+mod strict;
+#[cfg(feature = "derive")]
+pub mod typed;
+#[cfg(feature = "watch")]
+pub mod watch;
 pub type ConfigMap = HashMap<String, Value>;
 
 pub use manager::{ConfigManager, ConfigManagerBuilder};
+pub use merge::ArrayMergeStrategy;
+pub use shared::SharedConfig;
+#[cfg(feature = "derive")]
+pub use typed::Config;
+
+// Lets the code generated by `#[derive(Config)]`, which names `::rustic_config`, compile
+// inside this crate.
+#[cfg(feature = "derive")]
+extern crate self as rustic_config;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum FileType {
     Json,
     Yaml,
+    /// Requires the `toml` feature to be read.
+    Toml,
     Unsupported(FilePath),
 }
 
@@ -73,14 +121,69 @@ impl FilePath {
         FilePath(name.as_ref().to_string())
     }
 
+    /// Detects the file type from the extension, ignoring case. The file isn't read.
+    ///
+    /// Unknown and missing extensions yield [`FileType::Unsupported`]. When loading a file
+    /// without an extension, its content is sniffed with [`FileType::sniff`] instead.
     pub fn file_type(&self) -> FileType {
-        if self.0.ends_with(".yaml") {
-            FileType::Yaml
-        } else if self.0.ends_with(".json") {
-            FileType::Json
-        } else {
-            FileType::Unsupported(self.clone())
+        self.extension_type()
+            .unwrap_or_else(|| FileType::Unsupported(self.clone()))
+    }
+
+    // Detects the file type from the extension alone, or `None` if there is no extension.
+    pub(crate) fn extension_type(&self) -> Option<FileType> {
+        let extension = Path::new(&self.0).extension()?;
+        Some(
+            match extension.to_string_lossy().to_ascii_lowercase().as_str() {
+                "yaml" | "yml" => FileType::Yaml,
+                "json" => FileType::Json,
+                "toml" => FileType::Toml,
+                _ => FileType::Unsupported(self.clone()),
+            },
+        )
+    }
+}
+
+impl FileType {
+    /// Guesses the file type from the content of a configuration file, by parsing it.
+    ///
+    /// Content parsing as a JSON object is JSON, content parsing as a TOML table is TOML, and
+    /// content parsing as a YAML mapping is YAML. Without the `toml` feature, content that parses
+    /// as neither but opens with a `[table]` header or a `key = value` pair is still TOML.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::FileType;
+    ///
+    /// assert_eq!(FileType::sniff(r#"{"port": 80}"#), Some(FileType::Json));
+    /// assert_eq!(FileType::sniff("[server]\nport = 80"), Some(FileType::Toml));
+    /// assert_eq!(FileType::sniff("server:\n  port: 80"), Some(FileType::Yaml));
+    /// ```
+    pub fn sniff(content: &str) -> Option<FileType> {
+        let content = content.trim_start_matches('\u{feff}');
+        let first_line = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+
+        if first_line.starts_with('{') {
+            if let Ok(Value::Object(_)) = serde_json::from_str(content) {
+                return Some(FileType::Json);
+            }
+        }
+        #[cfg(feature = "toml")]
+        if toml::from_str::<toml::Table>(content).is_ok() {
+            return Some(FileType::Toml);
+        }
+        if let Ok(serde_yaml::Value::Mapping(_)) = serde_yaml::from_str(content) {
+            return Some(FileType::Yaml);
         }
+        #[cfg(not(feature = "toml"))]
+        if (first_line.starts_with('[') && first_line.ends_with(']')) || first_line.contains('=') {
+            return Some(FileType::Toml);
+        }
+        None
     }
 }
 
@@ -129,13 +232,78 @@ where
     Ok(())
 }
 
+// Converts a raw textual value (env var, cli flag) into the most specific JSON type it represents.
+// Text is only turned into a number if the number reads back the same, so values like `01234`,
+// `1.10` or IDs too large to represent exactly stay strings.
+pub(crate) fn coerce_value(raw: &str) -> Value {
+    let trimmed = raw.trim();
+    if trimmed.eq_ignore_ascii_case("true") {
+        return Value::Bool(true);
+    }
+    if trimmed.eq_ignore_ascii_case("false") {
+        return Value::Bool(false);
+    }
+    let number = if let Ok(i) = trimmed.parse::<i64>() {
+        Some(Number::from(i))
+    } else if let Ok(u) = trimmed.parse::<u64>() {
+        Some(Number::from(u))
+    } else {
+        trimmed.parse::<f64>().ok().and_then(Number::from_f64)
+    };
+    match number {
+        Some(n) if n.to_string() == trimmed => Value::Number(n),
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+// Inserts `value` under the nested `path`, creating (or replacing non-object) intermediate objects.
+pub(crate) fn insert_path(cfg_map: &mut ConfigMap, path: &[String], value: Value) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        cfg_map.insert(first.clone(), value);
+        return;
+    }
+    let mut current = cfg_map
+        .entry(first.clone())
+        .or_insert_with(|| Value::Object(Map::new()));
+    for segment in rest {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = current
+            .as_object_mut()
+            .unwrap()
+            .entry(segment.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    *current = value;
+}
+
 #[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    irrefutable_let_patterns,
+    non_snake_case,
+    unused_imports
+)]
 pub mod test {
-    use crate::{manager::ConfigManagerBuilder, FilePath};
+    use crate::{
+        cli_flags::CliFlagsReader,
+        coerce_value,
+        env_vars::EnvVarsReader,
+        manager::ConfigManagerBuilder,
+        merge::{merge_value, ArrayMergeStrategy},
+        FilePath, FileType,
+    };
     use serde::Deserialize;
     use serde_json::Number;
     const TEST_YAML_PATH: &str = "test/test.yaml";
     const TEST_JSON_PATH: &str = "test/test.json";
+    const TEST_OVERRIDE_PATH: &str = "test/override.yaml";
+    #[cfg(feature = "toml")]
+    const TEST_TOML_PATH: &str = "test/test.toml";
 
     #[test]
     pub fn setup_yaml() {
@@ -220,6 +388,1451 @@ pub mod test {
         assert_eq!(cm.values().collect::<Vec<_>>().len(), 5);
     }
 
+    #[test]
+    pub fn test_env_vars() {
+        let reader = EnvVarsReader::new().with_prefix("MYAPP_");
+        let cfg = reader
+            .read_from(vec![
+                (
+                    "MYAPP_DATABASE__URL".to_string(),
+                    "postgres://db".to_string(),
+                ),
+                ("MYAPP_DATABASE__POOL_SIZE".to_string(), "10".to_string()),
+                ("MYAPP_DEBUG".to_string(), "true".to_string()),
+                ("MYAPP_RATIO".to_string(), "0.5".to_string()),
+                ("OTHER_KEY".to_string(), "ignored".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(cfg["database"]["url"], "postgres://db");
+        assert_eq!(cfg["database"]["pool_size"], 10);
+        assert_eq!(cfg["debug"], true);
+        assert_eq!(cfg["ratio"], 0.5);
+        assert!(!cfg.contains_key("other_key"));
+
+        let reader = EnvVarsReader::new()
+            .with_alias("DATABASE_URL", "database.url")
+            .with_aliases_only();
+        let cfg = reader
+            .read_from(vec![
+                ("DATABASE_URL".to_string(), "postgres://db".to_string()),
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(cfg["database"]["url"], "postgres://db");
+        assert!(!cfg.contains_key("path"));
+
+        let cfg = EnvVarsReader::new()
+            .read_from(vec![("PATH".to_string(), "/usr/bin".to_string())])
+            .unwrap();
+        assert!(cfg.is_empty());
+    }
+
+    #[test]
+    pub fn test_coerce_value() {
+        assert_eq!(coerce_value("42"), 42);
+        assert_eq!(coerce_value("-7"), -7);
+        assert_eq!(coerce_value("18446744073709551615"), u64::MAX);
+        assert_eq!(coerce_value("0.5"), 0.5);
+        assert_eq!(coerce_value("TRUE"), true);
+        assert_eq!(coerce_value("01234"), "01234");
+        assert_eq!(coerce_value("1.10"), "1.10");
+        assert_eq!(coerce_value("1e3"), "1e3");
+        assert_eq!(coerce_value("+5"), "+5");
+        assert_eq!(
+            coerce_value("123456789012345678901234567890"),
+            "123456789012345678901234567890"
+        );
+        assert_eq!(coerce_value("inf"), "inf");
+        assert_eq!(coerce_value("nan"), "nan");
+    }
+
+    #[test]
+    pub fn test_env_source() {
+        std::env::set_var("RUSTIC_TEST_ENV_SOURCE_PORT", "8080");
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.env_prefix("RUSTIC_TEST_ENV_SOURCE_")
+            .add_source(crate::ConfigSource::Environment);
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_i64("port"), Some(8080));
+    }
+
+    #[test]
+    pub fn test_cli_flags() {
+        let args = [
+            "myapp",
+            "--port=8080",
+            "--host",
+            "localhost",
+            "--offset",
+            "-5",
+            "--verbose",
+            "file.txt",
+            "--no-color",
+            "--tag=a",
+            "--tag",
+            "b",
+            "--database.pool.size=10",
+            "positional",
+            "--",
+            "--ignored",
+        ]
+        .map(String::from);
+        let reader = CliFlagsReader::new()
+            .with_value_flag("host")
+            .with_value_flag("offset")
+            .with_value_flag("tag");
+        let cfg = reader.read(&args).unwrap();
+        assert_eq!(cfg["port"], 8080);
+        assert_eq!(cfg["host"], "localhost");
+        assert_eq!(cfg["offset"], -5);
+        assert_eq!(cfg["verbose"], true);
+        assert!(!cfg.contains_key("file.txt"));
+        assert_eq!(cfg["color"], false);
+        assert_eq!(cfg["tag"], serde_json::json!(["a", "b"]));
+        assert_eq!(cfg["database"]["pool"]["size"], 10);
+        assert!(!cfg.contains_key("ignored"));
+
+        let cfg = CliFlagsReader::new()
+            .read(&["--verbose", "file.txt"].map(String::from))
+            .unwrap();
+        assert_eq!(cfg["verbose"], true);
+        assert!(CliFlagsReader::new()
+            .with_value_flag("host")
+            .read(&["--host".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    pub fn test_cli_source() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::CommandLine(vec![
+            "--SOME_INT=5".to_string()
+        ]));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_i64("SOME_INT"), Some(5));
+    }
+
+    #[test]
+    pub fn test_source_order() {
+        let cli = crate::ConfigSource::CommandLine(vec!["--SOME_INT=5".to_string()]);
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(cli.clone())
+            .add_source(crate::ConfigSource::File(FilePath::new(TEST_YAML_PATH)));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_i64("SOME_INT"), Some(1));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source_with_priority(cli, 10)
+            .add_source(crate::ConfigSource::File(FilePath::new(TEST_YAML_PATH)));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_i64("SOME_INT"), Some(5));
+    }
+
+    #[test]
+    pub fn test_deep_merge() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(TEST_YAML_PATH)))
+            .add_source(crate::ConfigSource::File(FilePath::new(TEST_OVERRIDE_PATH)));
+        let cfg = cmb.build().unwrap();
+        let obj = cfg.get_object("SOME_OBJ").unwrap();
+        assert_eq!(obj["TEST_KEY_INT"], 2);
+        assert_eq!(obj["TEST_KEY_FLOAT"], 1.1);
+        assert_eq!(obj["TEST_KEY_VEC"], serde_json::json!(["4"]));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.array_merge_strategy(ArrayMergeStrategy::Append)
+            .add_source(crate::ConfigSource::File(FilePath::new(TEST_YAML_PATH)))
+            .add_source(crate::ConfigSource::File(FilePath::new(TEST_OVERRIDE_PATH)));
+        let cfg = cmb.build().unwrap();
+        let new_vec: Vec<String> = cfg.get_object("SOME_OBJ").unwrap()["TEST_KEY_VEC"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(new_vec, vec!["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    pub fn test_array_merge_strategies() {
+        use serde_json::json;
+
+        let mut base = json!([1, 2, 3]);
+        merge_value(&mut base, json!([4]), &ArrayMergeStrategy::MergeByIndex);
+        assert_eq!(base, json!([4, 2, 3]));
+
+        let strategy = ArrayMergeStrategy::MergeByKey("name".to_string());
+        let mut base = json!([{"name": "a", "port": 1}, {"name": "b", "port": 2}]);
+        merge_value(
+            &mut base,
+            json!([{"name": "b", "port": 3}, {"name": "c", "port": 4}]),
+            &strategy,
+        );
+        assert_eq!(
+            base,
+            json!([{"name": "a", "port": 1}, {"name": "b", "port": 3}, {"name": "c", "port": 4}])
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    pub fn setup_toml() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(TEST_TOML_PATH)));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_i64("some_int"), Some(7));
+        assert_eq!(cfg.get_str("some_string"), Some("Hello TOML!"));
+        assert_eq!(cfg.get_object("database").unwrap()["pool_size"], 10);
+        assert_eq!(cfg.get_str("released"), Some("2023-01-01T00:00:00Z"));
+    }
+
+    #[cfg(not(feature = "toml"))]
+    #[test]
+    pub fn toml_feature_disabled() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new("test/test.toml")));
+        let cm = cmb.build();
+        assert_eq!(
+            cm.unwrap_err(),
+            crate::error::ConfigError::FeatureNotSupported(crate::FileType::Toml)
+        );
+    }
+
+    #[test]
+    pub fn test_file_type_detection() {
+        assert_eq!(FilePath::new("config.yml").file_type(), FileType::Yaml);
+        assert_eq!(FilePath::new("CONFIG.YAML").file_type(), FileType::Yaml);
+        assert_eq!(FilePath::new("Config.Json").file_type(), FileType::Json);
+        assert_eq!(FilePath::new("Config.TOML").file_type(), FileType::Toml);
+        assert_eq!(
+            FilePath::new("config.ini").file_type(),
+            FileType::Unsupported(FilePath::new("config.ini"))
+        );
+        assert_eq!(
+            FilePath::new("test/config").file_type(),
+            FileType::Unsupported(FilePath::new("test/config"))
+        );
+    }
+
+    #[test]
+    pub fn test_sniff() {
+        assert_eq!(FileType::sniff("a=b: 1\n"), Some(FileType::Yaml));
+        assert_eq!(FileType::sniff("\"a=b\": 1\n"), Some(FileType::Yaml));
+        assert_eq!(
+            FileType::sniff("url = \"http://localhost\"\n"),
+            Some(FileType::Toml)
+        );
+        assert_eq!(FileType::sniff("just some text"), None);
+        assert_eq!(FileType::sniff("# only a comment\n"), None);
+    }
+
+    #[test]
+    pub fn setup_sniffed() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new("test/config")));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("service_name"), Some("sniffed"));
+    }
+
+    #[test]
+    pub fn no_extensionless_file_error() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new("no_file")));
+        let cm = cmb.build();
+        assert_eq!(
+            cm.unwrap_err().to_string(),
+            "Failed to read configuration file: No such file or directory (os error 2) [no_file]"
+        );
+    }
+
+    #[test]
+    pub fn test_path_lookup() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(TEST_YAML_PATH)));
+        let mut cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_i64("SOME_OBJ.TEST_KEY_INT"), Some(1));
+        assert_eq!(cfg.get_f64("/SOME_OBJ/TEST_KEY_FLOAT"), Some(1.1));
+        assert_eq!(cfg.get_str("SOME_OBJ.TEST_KEY_VEC[2]"), Some("3"));
+        assert_eq!(cfg.get_str("/SOME_OBJ/TEST_KEY_VEC/0"), Some("1"));
+        assert_eq!(cfg.try_get("SOME_VEC[1]").unwrap(), "2");
+        assert!(cfg.try_get("SOME_VEC[3]").is_err());
+        assert!(cfg.get_i64("SOME_OBJ..TEST_KEY_INT").is_none());
+        let vec: Vec<String> = cfg.get_struct("SOME_OBJ.TEST_KEY_VEC").unwrap();
+        assert_eq!(vec.len(), 3);
+
+        *cfg.get_mut("SOME_OBJ.TEST_KEY_INT").unwrap() = 5.into();
+        assert_eq!(cfg.get_i64("SOME_OBJ.TEST_KEY_INT"), Some(5));
+        assert_eq!(cfg.take("SOME_OBJ.TEST_KEY_INT"), 5);
+        assert_eq!(cfg.take("MISSING.KEY"), serde_json::Value::Null);
+    }
+
+    #[test]
+    pub fn test_provenance() {
+        use crate::provenance::Location;
+        use crate::ConfigSource;
+
+        let yaml = ConfigSource::File(FilePath::new(TEST_YAML_PATH));
+        let override_yaml = ConfigSource::File(FilePath::new(TEST_OVERRIDE_PATH));
+        let json = ConfigSource::File(FilePath::new(TEST_JSON_PATH));
+        let cli = ConfigSource::CommandLine(vec!["--SOME_INT=5".to_string()]);
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(yaml.clone())
+            .add_source(override_yaml.clone())
+            .add_source(json.clone())
+            .add_source(cli.clone());
+        let cfg = cmb.build().unwrap();
+
+        let provenance = cfg.source_of("SOME_OBJ.TEST_KEY_INT").unwrap();
+        assert_eq!(provenance.source, override_yaml);
+        assert_eq!(provenance.location, Some(Location { line: 2, column: 3 }));
+        assert_eq!(provenance.overridden.len(), 1);
+        assert_eq!(provenance.overridden[0].source, yaml);
+        assert_eq!(
+            provenance.overridden[0].location,
+            Some(Location { line: 9, column: 3 })
+        );
+        assert_eq!(provenance.overridden[0].value, 1);
+
+        let provenance = cfg.source_of("/SOME_OBJ/TEST_KEY_FLOAT").unwrap();
+        assert_eq!(provenance.source, yaml);
+        assert!(provenance.overridden.is_empty());
+
+        let provenance = cfg.source_of("someVec[1]").unwrap();
+        assert_eq!(provenance.source, json);
+        assert_eq!(provenance.location, Some(Location { line: 5, column: 5 }));
+
+        let provenance = cfg.source_of("SOME_INT").unwrap();
+        assert_eq!(provenance.source, cli);
+        assert_eq!(provenance.location, None);
+        assert_eq!(provenance.overridden[0].value, 1);
+
+        assert!(cfg.source_of("SOME_OBJ.MISSING").is_none());
+
+        // A scalar replacing an object overrides every value below it, and the other way round.
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(yaml.clone())
+            .add_source(ConfigSource::CommandLine(vec![
+                "--SOME_OBJ=flat".to_string(),
+                "--SOME_INT.nested=2".to_string(),
+            ]));
+        let cfg = cmb.build().unwrap();
+        let provenance = cfg.source_of("SOME_OBJ").unwrap();
+        assert_eq!(
+            provenance
+                .overridden
+                .iter()
+                .map(|o| o.value.clone())
+                .collect::<Vec<_>>(),
+            [
+                serde_json::json!(1.1),
+                serde_json::json!(1),
+                serde_json::json!(["1", "2", "3"])
+            ]
+        );
+        let provenance = cfg.source_of("SOME_INT.nested").unwrap();
+        assert_eq!(provenance.overridden[0].value, 1);
+    }
+
+    #[test]
+    pub fn test_custom_reader() {
+        use crate::file_reader::{Reader, YamlConfigReader};
+
+        // Readers written against `read` alone keep working.
+        struct EmptyReader;
+
+        impl Reader for EmptyReader {
+            fn read(&self, _path: &str) -> Result<crate::ConfigMap, crate::error::ConfigError> {
+                Ok(crate::ConfigMap::new())
+            }
+        }
+
+        assert!(EmptyReader.read(TEST_YAML_PATH).unwrap().is_empty());
+        assert!(EmptyReader.parse("a: 1").is_err());
+        assert!(EmptyReader.locate("a: 1").is_empty());
+        assert_eq!(
+            YamlConfigReader.read(TEST_YAML_PATH).unwrap()["SOME_INT"],
+            1
+        );
+    }
+
+    #[test]
+    pub fn test_dotted_key_provenance() {
+        use crate::provenance::Location;
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new("test/dotted.yaml")));
+        let cfg = cmb.build().unwrap();
+        // The key `a.b` and the key `b` nested under `a` are told apart.
+        let provenance = cfg.source_of("a.b").unwrap();
+        assert_eq!(provenance.value, 1);
+        assert_eq!(provenance.location, Some(Location { line: 1, column: 1 }));
+        assert!(provenance.overridden.is_empty());
+        let provenance = cfg.source_of("/a/b").unwrap();
+        assert_eq!(provenance.value, 2);
+        assert_eq!(provenance.location, Some(Location { line: 3, column: 3 }));
+        assert!(provenance.overridden.is_empty());
+
+        let locations = crate::provenance::locate_json("{\"a.b\": 1, \"a\": {\"b\": 2}}");
+        assert_eq!(locations["/a.b"], Location { line: 1, column: 2 });
+        assert_eq!(
+            locations["/a/b"],
+            Location {
+                line: 1,
+                column: 18
+            }
+        );
+        #[cfg(feature = "toml")]
+        {
+            let locations = crate::provenance::locate_toml("\"a.b\" = 1\n[a]\nb = 2\n");
+            assert_eq!(locations["/a.b"], Location { line: 1, column: 1 });
+            assert_eq!(locations["/a/b"], Location { line: 3, column: 1 });
+        }
+    }
+
+    #[test]
+    pub fn test_yaml_locations() {
+        use crate::provenance::{locate_yaml, Location};
+
+        let locations = locate_yaml(
+            "a: {b: 1, c: 2}\n\
+             d:\n  {e: 3}\n\
+             f: [\n  {g: 4},\n  {h: 5}\n]\n\
+             i:\n  j: 6\n",
+        );
+        let mut keys = locations.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, ["/a", "/d", "/f", "/i", "/i/j"]);
+        assert_eq!(locations["/i/j"], Location { line: 9, column: 3 });
+
+        let locations = locate_yaml("---\na: 1\n...\n");
+        assert_eq!(locations["/a"], Location { line: 2, column: 1 });
+        // Keys of later documents would be reported against the wrong values.
+        assert!(locate_yaml("a: 1\n---\nb: 2\n").is_empty());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    pub fn test_toml_provenance() {
+        use crate::provenance::Location;
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(TEST_TOML_PATH)));
+        let cfg = cmb.build().unwrap();
+        let provenance = cfg.source_of("database.pool_size").unwrap();
+        assert_eq!(provenance.location, Some(Location { line: 7, column: 1 }));
+    }
+
+    // A directory of its own under the system temp dir, removed with its content when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            // Tests run in parallel, so two of them may ask for the same name.
+            static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!(
+                "rustic_config_{}_{}_{}",
+                name,
+                std::process::id(),
+                count
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn join<P: AsRef<std::path::Path>>(&self, name: P) -> std::path::PathBuf {
+            self.0.join(name)
+        }
+
+        // Writes the file `name` in the directory, returning its path.
+        fn write(&self, name: &str, content: &str) -> FilePath {
+            let path = self.join(name);
+            std::fs::write(&path, content).unwrap();
+            FilePath::new(path.to_str().unwrap())
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_watch_and_reload() {
+        use std::time::Duration;
+
+        let dir = TempDir::new("reload");
+        let path = dir.write("config.yaml", "port: 80\nhost: localhost\n");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let cm = cmb.build().unwrap();
+        let (term_tx, term_rx) = oneshot::channel();
+        let (shared, reloads, handle) = cm.watch_and_reload(term_rx).unwrap();
+
+        std::fs::write(path.as_ref(), "port: 8080\nhost: localhost\n").unwrap();
+        // A single save may produce several events, wait for the one completing the write.
+        while shared.snapshot().get_i64("port") != Some(8080) {
+            let _ = reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+
+        // A broken file keeps the last configuration. Appending avoids reloading a truncated file.
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path.as_ref())
+            .unwrap();
+        std::io::Write::write_all(&mut file, b"port: [8081\n").unwrap();
+        while reloads
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .is_ok()
+        {}
+        assert_eq!(shared.snapshot().get_i64("port"), Some(8080));
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+        // The stopped watcher no longer reports anything.
+        while reloads.try_recv().is_ok() {}
+        assert_eq!(
+            reloads.try_recv(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected)
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_watch_all_files() {
+        use std::time::Duration;
+
+        let dir = TempDir::new("watch");
+        let base = dir.write("base.yaml", "port: 80\n");
+        let prod = dir.write("prod.json", "{\"host\": \"localhost\"}");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::Environment)
+            .add_source(crate::ConfigSource::File(base.clone()))
+            .add_source(crate::ConfigSource::File(prod.clone()));
+        let cm = cmb.build().unwrap();
+        let (term_tx, term_rx) = oneshot::channel();
+        let (events, handle) = cm.watch_file_changes(term_rx).unwrap();
+
+        std::fs::write(prod.as_ref(), "{\"host\": \"example.com\"}").unwrap();
+        let event = events
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.paths, vec![prod]);
+
+        // Drain the remaining events of the first save before the second one.
+        while events.recv_timeout(Duration::from_millis(200)).is_ok() {}
+        std::fs::write(base.as_ref(), "port: 8080\n").unwrap();
+        let event = events
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.paths, vec![base]);
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_subscribe() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let dir = TempDir::new("sub");
+        let path = dir.write(
+            "config.yaml",
+            "database:\n  pool_size: 5\nhost: localhost\n",
+        );
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let cm = cmb.build().unwrap();
+        let host_changes = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&host_changes);
+        cm.subscribe("host", move |_| *counter.lock().unwrap() += 1);
+        let pool_size = cm.subscribe_channel("database.pool_size");
+
+        let (term_tx, term_rx) = oneshot::channel();
+        let (_shared, _reloads, handle) = cm.watch_and_reload(term_rx).unwrap();
+
+        // Overwrite the pool size in place, so no truncated intermediate content is ever read.
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path.as_ref())
+            .unwrap();
+        std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(23)).unwrap();
+        std::io::Write::write_all(&mut file, b"9").unwrap();
+        let change = pool_size.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change.key, "database.pool_size");
+        assert_eq!(change.old, 5);
+        assert_eq!(change.new, 9);
+        assert_eq!(*host_changes.lock().unwrap(), 0);
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_unsubscribe_in_callback() {
+        use std::sync::{Arc, Mutex};
+
+        let dir = TempDir::new("unsub");
+        let path = dir.write("config.yaml", "port: 80\n");
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let shared = cmb.build().unwrap().into_shared();
+
+        // The subscriber removes itself on the first change it sees.
+        let calls = Arc::new(Mutex::new(0));
+        let id = Arc::new(Mutex::new(None));
+        let (counter, own_id, handle) = (Arc::clone(&calls), Arc::clone(&id), shared.clone());
+        let sub = shared.snapshot().subscribe("port", move |_| {
+            *counter.lock().unwrap() += 1;
+            let id = own_id.lock().unwrap().take().unwrap();
+            assert!(handle.snapshot().unsubscribe(id));
+        });
+        *id.lock().unwrap() = Some(sub);
+
+        for port in [81, 82] {
+            std::fs::write(path.as_ref(), format!("port: {port}\n")).unwrap();
+            shared.reload().unwrap();
+        }
+        assert_eq!(*calls.lock().unwrap(), 1);
+        assert!(!shared.snapshot().unsubscribe(sub));
+    }
+
+    #[test]
+    pub fn test_validators() {
+        #[derive(Deserialize)]
+        struct Cfg {
+            #[allow(dead_code)]
+            #[serde(rename = "someInt")]
+            some_int: u8,
+        }
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(TEST_JSON_PATH)))
+            .validate_as::<Cfg>()
+            .add_validator(|cm| match cm.get_i64("someInt") {
+                Some(i) if i > 100 => Err(crate::error::ConfigError::ValidationError(
+                    "someInt too large".to_string(),
+                )),
+                _ => Ok(()),
+            });
+        assert!(cmb.clone().build().is_ok());
+
+        cmb.add_source(crate::ConfigSource::CommandLine(vec![
+            "--someInt=200".to_string()
+        ]));
+        assert_eq!(
+            cmb.clone().build().unwrap_err(),
+            crate::error::ConfigError::ValidationError("someInt too large".to_string())
+        );
+
+        cmb.add_source(crate::ConfigSource::CommandLine(vec![
+            "--someInt=-1".to_string()
+        ]));
+        assert!(matches!(
+            cmb.build().unwrap_err(),
+            crate::error::ConfigError::ParseError(_)
+        ));
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_reload_rollback() {
+        use std::time::Duration;
+
+        #[derive(Deserialize)]
+        struct Cfg {
+            #[allow(dead_code)]
+            port: u16,
+        }
+
+        let dir = TempDir::new("rollback");
+        let path = dir.write("config.yaml", "port: 8080\n");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .validate_as::<Cfg>();
+        let cm = cmb.build().unwrap();
+        let (term_tx, term_rx) = oneshot::channel();
+        let (shared, reloads, handle) = cm.watch_and_reload(term_rx).unwrap();
+
+        // "80x0" is valid YAML but not a port.
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path.as_ref())
+            .unwrap();
+        std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(8)).unwrap();
+        std::io::Write::write_all(&mut file, b"x").unwrap();
+        let outcome = reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(
+            outcome,
+            Err(crate::error::ConfigError::ParseError(_))
+        ));
+        assert_eq!(shared.snapshot().get_i64("port"), Some(8080));
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_watch_debounce() {
+        use std::time::Duration;
+
+        let dir = TempDir::new("debounce");
+        let base = dir.write("base.yaml", "port: 80\n");
+        let prod = dir.write("prod.yaml", "host: localhost\n");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(base.clone()))
+            .add_source(crate::ConfigSource::File(prod.clone()))
+            .watch_debounce(Duration::from_millis(300));
+        let cm = cmb.build().unwrap();
+        let (term_tx, term_rx) = oneshot::channel();
+        let (events, handle) = cm.watch_file_changes(term_rx).unwrap();
+
+        // A burst of saves to both files within the window is reported once.
+        for i in 0..3 {
+            std::fs::write(base.as_ref(), format!("port: 8{}\n", i)).unwrap();
+            std::fs::write(prod.as_ref(), format!("host: host{}\n", i)).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let event = events
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.paths.len(), 2);
+        assert!(event.paths.contains(&base) && event.paths.contains(&prod));
+        assert!(event.events.len() > 1);
+        assert!(events.recv_timeout(Duration::from_millis(600)).is_err());
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(all(feature = "watch", unix))]
+    #[test]
+    pub fn test_watch_symlink_swap() {
+        use std::os::unix::fs::symlink;
+        use std::time::Duration;
+
+        // Lay out the directory like a mounted ConfigMap: `config.yaml -> ..data/config.yaml`
+        // and `..data -> ..v0`, updated by atomically renaming a new `..data` link into place.
+        let dir = TempDir::new("k8s");
+        let swap = |version: u32| {
+            let data = dir.join(format!("..v{}", version));
+            std::fs::create_dir_all(&data).unwrap();
+            std::fs::write(data.join("config.yaml"), format!("version: {}\n", version)).unwrap();
+            symlink(format!("..v{}", version), dir.join("..data_tmp")).unwrap();
+            std::fs::rename(dir.join("..data_tmp"), dir.join("..data")).unwrap();
+            if version > 0 {
+                std::fs::remove_dir_all(dir.join(format!("..v{}", version - 1))).unwrap();
+            }
+        };
+        swap(0);
+        symlink("..data/config.yaml", dir.join("config.yaml")).unwrap();
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            dir.join("config.yaml").to_str().unwrap(),
+        )));
+        let cm = cmb.build().unwrap();
+        let (term_tx, term_rx) = oneshot::channel();
+        let (shared, reloads, handle) = cm.watch_and_reload(term_rx).unwrap();
+
+        // The watch must survive each swap, including the removal of the previous data directory.
+        for version in 1..=2 {
+            swap(version);
+            while shared.snapshot().get_i64("version") != Some(version.into()) {
+                let _ = reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+            }
+        }
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_watch_polling() {
+        use std::time::Duration;
+
+        let dir = TempDir::new("poll");
+        let path = dir.write("config.yaml", "port: 80\n");
+        let modified = std::fs::metadata(path.as_ref())
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .watch_poll_interval(Duration::from_millis(100));
+        let cm = cmb.build().unwrap();
+        let (term_tx, term_rx) = oneshot::channel();
+        let (shared, reloads, handle) = cm.watch_and_reload(term_rx).unwrap();
+
+        // Changes are noticed by content, even if the modification time stays the same.
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path.as_ref())
+            .unwrap();
+        std::io::Write::write_all(&mut &file, b"port: 81\n").unwrap();
+        file.set_modified(modified).unwrap();
+        while shared.snapshot().get_i64("port") != Some(81) {
+            let _ = reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+
+        term_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    pub async fn test_build_async() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(TEST_YAML_PATH)))
+            .add_source(crate::ConfigSource::File(FilePath::new("test/config")));
+        let cfg = cmb.build_async().await.unwrap();
+        assert_eq!(cfg.get_i64("SOME_OBJ.TEST_KEY_INT"), Some(1));
+        assert_eq!(cfg.get_str("service_name"), Some("sniffed"));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/include/app.yaml",
+        )));
+        let cfg = cmb.build_async().await.unwrap();
+        assert_eq!(cfg.get_i64("database.pool_size"), Some(20));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new("no_file.yaml")));
+        assert!(cmb.build_async().await.is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    pub async fn test_reload_stream() {
+        use futures::StreamExt;
+        use std::time::Duration;
+
+        let dir = TempDir::new("stream");
+        let path = dir.write("config.yaml", "port: 80\n");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let cm = cmb.build_async().await.unwrap();
+        let (shared, mut reloads) = cm.watch_and_reload_stream().unwrap();
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path.as_ref())
+            .unwrap();
+        std::io::Write::write_all(&mut file, b"port: 81\n").unwrap();
+        while shared.snapshot().get_i64("port") != Some(81) {
+            let next = tokio::time::timeout(Duration::from_secs(5), reloads.next());
+            next.await.unwrap().unwrap().unwrap();
+        }
+
+        // Stopping the stream stops the watcher, so later changes aren't picked up anymore.
+        let handle = reloads.stop();
+        tokio::task::spawn_blocking(move || handle.join())
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::write(path.as_ref(), "port: 82\n").unwrap();
+        assert_eq!(shared.snapshot().get_i64("port"), Some(81));
+    }
+
+    #[test]
+    pub fn test_shared_config() {
+        let dir = TempDir::new("shared");
+        let path = dir.write("config.yaml", "port: 80\n");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let shared = cmb.build().unwrap().into_shared();
+        let before = shared.snapshot();
+
+        std::fs::write(path.as_ref(), "port: 81\n").unwrap();
+        let worker = shared.clone();
+        std::thread::spawn(move || worker.reload().unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(before.get_i64("port"), Some(80));
+        assert_eq!(shared.snapshot().get_i64("port"), Some(81));
+
+        // A failed reload keeps the current version.
+        std::fs::write(path.as_ref(), "port: [81\n").unwrap();
+        assert!(shared.reload().is_err());
+        assert_eq!(shared.snapshot().get_i64("port"), Some(81));
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    pub fn test_watch_errors() {
+        use std::time::Duration;
+
+        let dir = TempDir::new("errors");
+        let path = dir.write("config.yaml", "port: 80\n");
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let cm = cmb.build().unwrap();
+
+        // Setup failures are returned right away.
+        std::fs::remove_file(path.as_ref()).unwrap();
+        let (_term_tx, term_rx) = oneshot::channel();
+        assert!(matches!(
+            cm.watch_file_changes(term_rx),
+            Err(crate::error::ConfigError::FileWatchError(_))
+        ));
+
+        // Dropping the receiver stops the watcher on its next event.
+        std::fs::write(path.as_ref(), "port: 80\n").unwrap();
+        let (_term_tx, term_rx) = oneshot::channel();
+        let (events, handle) = cm.watch_file_changes(term_rx).unwrap();
+        drop(events);
+        std::fs::write(path.as_ref(), "port: 81\n").unwrap();
+        for _ in 0..50 {
+            if handle.is_finished() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(handle.is_finished());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_interpolation() {
+        std::env::set_var("RUSTIC_CONFIG_TEST_HOME", "/home/rustic");
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/interpolate.yaml",
+        )))
+        .interpolation(true);
+        let cfg = cmb.build().unwrap();
+        assert_eq!(
+            cfg.get_str("database.url"),
+            Some("postgres://db.internal:5432/app")
+        );
+        assert_eq!(cfg.get_i64("database.replica_port"), Some(5432));
+        assert_eq!(cfg.get_str("home"), Some("/home/rustic"));
+        assert_eq!(cfg.get_str("user"), Some("nobody"));
+        assert_eq!(cfg.get_i64("timeout"), Some(30));
+        assert_eq!(cfg.get_str("literal"), Some("${not.a.reference}"));
+        assert_eq!(cfg.get_str("hosts[0]"), Some("db.internal"));
+        assert_eq!(cfg.get_str("primary"), Some("db.internal"));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/interpolate.yaml",
+        )));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("primary"), Some("${hosts[0]}"));
+
+        // Configurations written before interpolation existed keep their `${...}` text.
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/template.yaml",
+        )));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("greeting"), Some("Hello ${user.name}!"));
+        assert_eq!(cfg.get_str("command"), Some("${HOME}/bin/run"));
+    }
+
+    #[test]
+    pub fn test_interpolation_errors() {
+        let build = |args: &[&str]| {
+            let mut cmb = ConfigManagerBuilder::new();
+            cmb.add_source(crate::ConfigSource::CommandLine(
+                args.iter().map(|arg| arg.to_string()).collect(),
+            ))
+            .interpolation(true);
+            cmb.build().unwrap_err().to_string()
+        };
+        assert_eq!(
+            build(&["--a=${b}", "--b=x${c}", "--c=${a}"]),
+            "Failed to interpolate configuration: Reference cycle: a -> b -> c -> a"
+        );
+        assert_eq!(
+            build(&["--db.url=${db.host}/app"]),
+            "Failed to interpolate configuration: `db.host` is not defined (in `db.url`)"
+        );
+        assert_eq!(
+            build(&["--db.host=x", "--url=http://${db}"]),
+            "Failed to interpolate configuration: `db` is not a string, number or boolean \
+             and can't be embedded (in `url`)"
+        );
+    }
+
+    #[test]
+    pub fn test_includes() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/include/app.yaml",
+        )));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("service"), Some("app"));
+        assert_eq!(cfg.get_str("log_level"), Some("info"));
+        assert_eq!(cfg.get_i64("database.pool_size"), Some(20));
+        assert_eq!(cfg.get_i64("database.port"), Some(5432));
+        assert_eq!(cfg.get_str("database.host"), Some("replica.internal"));
+        assert_eq!(cfg.get_bool("extra"), Some(true));
+        assert_eq!(cfg.get_bool("feature.enabled"), Some(true));
+        assert!(cfg.try_get("$include").is_err());
+        assert_eq!(
+            cfg.source_of("database.host").unwrap().overridden[0].source,
+            crate::ConfigSource::File(FilePath::new("test/include/db/primary.yaml"))
+        );
+        assert_eq!(
+            cfg.source_of("feature.enabled").unwrap().source,
+            crate::ConfigSource::File(FilePath::new("test/include/nested/feature.yaml"))
+        );
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/include/cycle_a.yaml",
+        )));
+        assert_eq!(
+            cmb.build().unwrap_err().to_string(),
+            "Failed to include configuration file: Include cycle: test/include/cycle_a.yaml -> \
+             test/include/cycle_b.yaml -> test/include/cycle_a.yaml"
+        );
+    }
+
+    #[test]
+    pub fn test_profiles() {
+        let path = FilePath::new("test/profile/config.yaml");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()));
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("server.host"), Some("localhost"));
+        assert_eq!(cfg.get_i64("server.port"), Some(8080));
+        // Without profiles in use, `profiles` is an ordinary key.
+        assert_eq!(cfg.get_str("profiles.prod.server.host"), Some("0.0.0.0"));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .profile_env("RUSTIC_CONFIG_TEST_PROFILE_UNSET");
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("server.host"), Some("localhost"));
+        assert!(cfg.try_get("profiles").is_err());
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .profile("prod");
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("server.host"), Some("0.0.0.0"));
+        assert_eq!(cfg.get_i64("server.port"), Some(443));
+        assert_eq!(cfg.get_str("log_level"), Some("warn"));
+        assert_eq!(
+            cfg.source_of("server.port").unwrap().source,
+            crate::ConfigSource::File(FilePath::new("test/profile/config.prod.yaml"))
+        );
+        assert_eq!(
+            cfg.source_of("server.host").unwrap().location.unwrap().line,
+            11
+        );
+
+        std::env::set_var("RUSTIC_CONFIG_TEST_PROFILE", "staging");
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .profile_env("RUSTIC_CONFIG_TEST_PROFILE");
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.get_str("server.host"), Some("staging.internal"));
+        assert_eq!(cfg.get_i64("server.port"), Some(8080));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path))
+            .profile_env("RUSTIC_CONFIG_TEST_PROFILE")
+            .profile("qa");
+        assert!(matches!(
+            cmb.build(),
+            Err(crate::error::ConfigError::ProfileNotFoundError(p)) if p == "qa"
+        ));
+    }
+
+    #[test]
+    pub fn test_profiles_key_not_a_map() {
+        let path = FilePath::new("test/profile/roles.yaml");
+        let roles = serde_json::json!(["admin", "guest"]);
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .profile_env("RUSTIC_CONFIG_TEST_PROFILE_UNSET");
+        let cfg = cmb.build().unwrap();
+        assert_eq!(cfg.try_get("profiles").unwrap(), &roles);
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path))
+            .profile("admin");
+        assert!(matches!(
+            cmb.build(),
+            Err(crate::error::ConfigError::ProfileNotFoundError(p)) if p == "admin"
+        ));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    pub fn test_schema_validation() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/schema/config.yaml",
+        )))
+        .add_source(crate::ConfigSource::CommandLine(vec![
+            "--server.host=7".to_string()
+        ]))
+        .schema_file(FilePath::new("test/schema/config.schema.json"));
+        let err = cmb.build().unwrap_err();
+        let crate::error::ConfigError::ValidationError(message) = err else {
+            panic!("expected a validation error, got {:?}", err);
+        };
+        let mut lines = message.lines().skip(1).map(str::trim).collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "<root>: \"name\" is a required property",
+                "server.host: 7 is not of type \"string\" (from command-line arguments)",
+                "server.port: \"eighty\" is not of type \"integer\" \
+                 (from test/schema/config.yaml:3:3)",
+                "workers: 0 is less than the minimum of 1 (from test/schema/config.yaml:4:1)",
+            ]
+        );
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/schema/config.yaml",
+        )))
+        .schema(serde_json::json!({
+            "type": "object",
+            "properties": { "server": { "type": "object" } }
+        }));
+        assert!(cmb.build().is_ok());
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    pub fn test_schema_for() {
+        #[derive(Deserialize, crate::JsonSchema)]
+        #[allow(dead_code)]
+        struct Server {
+            host: String,
+            #[serde(default)]
+            port: u16,
+        }
+
+        #[derive(Deserialize, crate::JsonSchema)]
+        #[allow(dead_code)]
+        struct AppConfig {
+            server: Server,
+            workers: u32,
+        }
+
+        let schema = crate::schema::schema_for::<AppConfig>();
+        assert_eq!(
+            schema["$schema"],
+            "https://json-schema.org/draft/2020-12/schema"
+        );
+        assert_eq!(schema["required"], serde_json::json!(["server", "workers"]));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new(
+            "test/schema/config.yaml",
+        )))
+        .schema_for::<AppConfig>();
+        let err = cmb.build().unwrap_err().to_string();
+        assert!(err.contains("server.port: \"eighty\" is not of type \"integer\""));
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::CommandLine(vec![
+            "--server.host=localhost".to_string(),
+            "--workers=4".to_string(),
+        ]))
+        .schema_for::<AppConfig>();
+        let cfg = cmb.build().unwrap();
+        assert!(cfg.parse::<AppConfig>().is_ok());
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    pub fn test_derive_config() {
+        use crate::{typed::ConfigField, Config};
+
+        #[derive(Config, Deserialize, Debug)]
+        struct Database {
+            #[config(env = "RUSTIC_CONFIG_TEST_DATABASE_URL", secret, non_empty)]
+            url: String,
+            #[config(default = 5, range(min = 1, max = 100))]
+            pool_size: u32,
+        }
+
+        #[derive(Config, Deserialize, Debug)]
+        #[config(file = "test/derive.yaml", env_prefix = "RUSTIC_CONFIG_TEST_DERIVE_")]
+        struct AppConfig {
+            /// Name of the service.
+            #[config(regex = "^[a-z-]+$")]
+            name: String,
+            #[config(default = 8080, cli = "port")]
+            #[serde(rename = "listen_port")]
+            port: u16,
+            #[config(nested)]
+            database: Database,
+            #[config(non_empty)]
+            tags: Vec<String>,
+            #[config(range(max = 10))]
+            retries: Option<u8>,
+        }
+
+        assert_eq!(
+            AppConfig::fields("")[..2],
+            [
+                ConfigField {
+                    key: "name".to_string(),
+                    doc: Some("Name of the service.".to_string()),
+                    env: None,
+                    cli: None,
+                    default: None,
+                    secret: false,
+                },
+                ConfigField {
+                    key: "listen_port".to_string(),
+                    doc: None,
+                    env: None,
+                    cli: Some("port".to_string()),
+                    default: Some(serde_json::json!(8080)),
+                    secret: false,
+                },
+            ]
+        );
+        assert_eq!(AppConfig::fields("")[2].key, "database.url");
+        assert!(AppConfig::fields("")[2].secret);
+
+        std::env::set_var("RUSTIC_CONFIG_TEST_DATABASE_URL", "postgres://db");
+        let mut builder = ConfigManagerBuilder::new();
+        builder.add_source(crate::ConfigSource::File(FilePath::new("test/derive.yaml")));
+        AppConfig::configure(&mut builder, "");
+        builder
+            .add_source(crate::ConfigSource::Environment)
+            .add_source(crate::ConfigSource::CommandLine(
+                ["--port", "9090", "input.txt"].map(String::from).to_vec(),
+            ));
+        let config = AppConfig::from_manager(&builder.build().unwrap()).unwrap();
+        assert_eq!(config.name, "billing");
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.database.url, "postgres://db");
+        assert_eq!(config.database.pool_size, 5);
+        assert_eq!(config.retries, None);
+
+        let mut builder = ConfigManagerBuilder::new();
+        builder
+            .add_source(crate::ConfigSource::File(FilePath::new("test/derive.yaml")))
+            .add_source(crate::ConfigSource::CommandLine(vec![
+                "--database.pool_sise=3".to_string(),
+            ]))
+            .strict(true);
+        AppConfig::configure(&mut builder, "");
+        assert!(builder.build().unwrap_err().to_string().ends_with(
+            "database.pool_sise (from command-line arguments), did you mean `pool_size`?"
+        ));
+
+        let invalid = AppConfig {
+            name: "Billing".to_string(),
+            port: 80,
+            database: Database {
+                url: " ".to_string(),
+                pool_size: 0,
+            },
+            tags: Vec::new(),
+            retries: Some(11),
+        };
+        assert_eq!(
+            invalid.validate().unwrap_err().to_string(),
+            "Validation error: Invalid configuration:\n  \
+             name: must match `^[a-z-]+$`, got \"Billing\"\n  \
+             database.url: must not be empty\n  \
+             database.pool_size: must be between 1 and 100, got 0\n  \
+             tags: must not be empty\n  \
+             retries: must be at most 10, got 11"
+        );
+
+        std::env::set_var("RUSTIC_CONFIG_TEST_DERIVE_RETRIES", "20");
+        assert!(matches!(
+            AppConfig::load(),
+            Err(crate::error::ConfigError::ValidationError(message))
+                if message.ends_with("retries: must be at most 10, got 20")
+        ));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    pub fn test_derive_config_rename_all() {
+        use crate::Config;
+
+        #[derive(Config, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Pool {
+            #[config(default = 5)]
+            max_size: u32,
+            #[serde(rename = "idle")]
+            idle_timeout: Option<u64>,
+        }
+
+        #[derive(Config, Deserialize, Debug)]
+        #[serde(rename_all = "kebab-case")]
+        struct AppConfig {
+            #[config(cli = "url")]
+            database_url: String,
+            #[config(nested)]
+            connection_pool: Pool,
+            r#type: Option<String>,
+        }
+
+        let keys = AppConfig::fields("")
+            .into_iter()
+            .map(|field| field.key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "database-url",
+                "connection-pool.maxSize",
+                "connection-pool.idle",
+                "type"
+            ]
+        );
+
+        let mut builder = ConfigManagerBuilder::new();
+        builder
+            .add_source(crate::ConfigSource::CommandLine(
+                [
+                    "--url",
+                    "postgres://db",
+                    "--connection-pool.idle",
+                    "30",
+                    "--type=worker",
+                ]
+                .map(String::from)
+                .to_vec(),
+            ))
+            .strict(true);
+        AppConfig::configure(&mut builder, "");
+        let config = AppConfig::from_manager(&builder.build().unwrap()).unwrap();
+        assert_eq!(config.database_url, "postgres://db");
+        assert_eq!(config.connection_pool.max_size, 5);
+        assert_eq!(config.connection_pool.idle_timeout, Some(30));
+        assert_eq!(config.r#type.as_deref(), Some("worker"));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    pub fn test_derive_config_env() {
+        use crate::Config;
+
+        #[derive(Config, Deserialize, Debug)]
+        struct AppConfig {
+            #[config(env = "RUSTIC_CONFIG_TEST_DERIVE_ENV_NAME")]
+            name: Option<String>,
+        }
+
+        std::env::set_var("RUSTIC_CONFIG_TEST_DERIVE_ENV_NAME", "billing");
+        let cm = AppConfig::builder().build().unwrap();
+        assert_eq!(cm.get_str("name"), Some("billing"));
+        // Without `env_prefix`, the rest of the environment stays out of the configuration.
+        assert!(cm.try_get("rustic_config_test_derive_env_name").is_err());
+        assert!(cm.try_get("path").is_err());
+        assert_eq!(AppConfig::load().unwrap().name.as_deref(), Some("billing"));
+    }
+
+    #[test]
+    pub fn test_strict_mode() {
+        let path = FilePath::new("test/strict.yaml");
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .known_keys(["database_url", "server.host", "server.port", "replicas"]);
+        assert!(cmb.clone().build().is_ok());
+
+        std::env::set_var("RUSTIC_CONFIG_TEST_STRICT_EXTRA", "1");
+        cmb.env_prefix("RUSTIC_CONFIG_TEST_STRICT_")
+            .add_source(crate::ConfigSource::Environment)
+            .add_source(crate::ConfigSource::CommandLine(vec![
+                "--verbose".to_string()
+            ]))
+            .strict(true);
+        assert_eq!(
+            cmb.build().unwrap_err().to_string(),
+            "Validation error: Unknown configuration keys:\n  \
+             databse_url (from test/strict.yaml:1:1), did you mean `database_url`?\n  \
+             server.prot (from test/strict.yaml:4:3), did you mean `port`?\n  \
+             verbose (from command-line arguments)"
+        );
+
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(path.clone()))
+            .strict(true);
+        assert!(cmb.build().is_err());
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    pub fn test_strict_mode_schema() {
+        let mut cmb = ConfigManagerBuilder::new();
+        cmb.add_source(crate::ConfigSource::File(FilePath::new("test/strict.yaml")))
+            .schema(serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "database_url": { "type": "string" },
+                    "server": { "$ref": "#/$defs/Server" },
+                    "replicas": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": { "host": { "type": "string" } }
+                        }
+                    },
+                    "labels": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    }
+                },
+                "$defs": {
+                    "Server": {
+                        "anyOf": [
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "host": { "type": "string" },
+                                    "port": { "type": "integer" }
+                                }
+                            },
+                            { "type": "null" }
+                        ]
+                    }
+                }
+            }))
+            .strict(true);
+        let err = cmb.build().unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Validation error: Unknown configuration keys:\n  \
+             databse_url (from test/strict.yaml:1:1), did you mean `database_url`?\n  \
+             replicas[0].weight (from test/strict.yaml:5:1)\n  \
+             server.prot (from test/strict.yaml:4:3), did you mean `port`?"
+        );
+    }
+
     // #[test]
     // pub fn test_file_watch() {
     //     let mut cmb = ConfigManagerBuilder::new();
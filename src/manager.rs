@@ -1,13 +1,34 @@
+use crate::cli_flags::CliFlagsReader;
+use crate::env_vars::EnvVarsReader;
+use crate::include::{read_with_includes, Layer};
+use crate::interpolate::interpolate;
+use crate::merge::{merge_maps, ArrayMergeStrategy};
+use crate::path::{pointer_join, KeyPath};
+use crate::profile::{profile_path, take_profile_section};
+use crate::provenance::{Provenance, ProvenanceTracker};
+#[cfg(feature = "schema")]
+use crate::schema::{self, Schema};
+use crate::shared::SharedConfig;
+use crate::strict::{check_unknown_keys, Expected};
+#[cfg(feature = "async")]
+use crate::watch::ReloadStream;
 #[cfg(feature = "watch")]
-use notify::{Event, RecommendedWatcher, RecursiveMode, Result as NotifyResult, Watcher};
-use std::{
-    collections::{hash_map, HashMap},
-    sync::mpsc::{channel, Receiver},
+use crate::watch::{
+    spawn_watcher, FileChangeEvent, ReloadReceiver, SubscriptionId, Subscriptions, ValueChange,
+    WatchHandle, WatchOptions,
 };
+use crate::{error::ConfigError, insert_path, ConfigMap, ConfigSource, FilePath};
+#[cfg(feature = "watch")]
+use notify::Result as NotifyResult;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{Map, Number, Value};
-use crate::file_reader::{JsonConfigReader, Reader, YamlConfigReader};
-use crate::{error::ConfigError, ConfigMap, ConfigSource, FilePath, FileType};
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+#[cfg(feature = "watch")]
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+#[cfg(feature = "watch")]
+use std::time::Duration;
 
 /// ConfigManagerBuilder is responsible for building the ConfigManager.
 /// It allows adding various configuration sources like environment variables, files, and command-line arguments.
@@ -21,21 +42,53 @@ use crate::{error::ConfigError, ConfigMap, ConfigSource, FilePath, FileType};
 /// builder.add_source(ConfigSource::File(FilePath::new("config.yaml")));
 /// let config_manager = builder.build().unwrap();
 /// ```
+#[derive(Debug, Clone)]
 pub struct ConfigManagerBuilder {
-    sources: HashMap<ConfigSource, ConfigMap>,
+    sources: Vec<(ConfigSource, i32)>,
+    defaults: ConfigMap,
+    env: EnvVarsReader,
+    cli: CliFlagsReader,
+    cli_aliases: Vec<(String, String)>,
+    array_merge: ArrayMergeStrategy,
+    validators: Vec<Validator>,
+    #[cfg(feature = "schema")]
+    schemas: Vec<Schema>,
+    known_keys: Option<Expected>,
+    strict: bool,
+    interpolate: bool,
+    profile: Option<String>,
+    profile_env: Option<String>,
+    #[cfg(feature = "watch")]
+    watch: WatchOptions,
+}
+
+type ValidatorFn = dyn Fn(&ConfigManager) -> Result<(), ConfigError> + Send + Sync;
+
+// A check the merged configuration must pass before it's handed out.
+#[derive(Clone)]
+struct Validator(Arc<ValidatorFn>);
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Validator")
+    }
 }
 
+// Reads `config.yaml` and, since no prefix is set, only the aliased environment variables.
 impl Default for ConfigManagerBuilder {
     fn default() -> Self {
         let mut cmb = ConfigManagerBuilder::new();
-        cmb.add_source(ConfigSource::Environment);
         cmb.add_source(ConfigSource::File(FilePath::new("config.yaml")));
+        cmb.add_source(ConfigSource::Environment);
         cmb
     }
 }
 
 /// A configuration manager builder, useful utility methods for crafting a [`ConfigManager`]
 impl ConfigManagerBuilder {
+    /// The priority given to sources added with [`ConfigManagerBuilder::add_source`].
+    pub const DEFAULT_PRIORITY: i32 = 0;
+
     /// Creates a new ConfigManagerBuilder with no sources added.
     ///
     /// # Examples
@@ -47,44 +100,231 @@ impl ConfigManagerBuilder {
     /// ```
     pub fn new() -> Self {
         Self {
-            sources: HashMap::new(),
+            sources: Vec::new(),
+            defaults: ConfigMap::new(),
+            env: EnvVarsReader::new(),
+            cli: CliFlagsReader::new(),
+            cli_aliases: Vec::new(),
+            array_merge: ArrayMergeStrategy::default(),
+            validators: Vec::new(),
+            #[cfg(feature = "schema")]
+            schemas: Vec::new(),
+            known_keys: None,
+            strict: false,
+            interpolate: false,
+            profile: None,
+            profile_env: None,
+            #[cfg(feature = "watch")]
+            watch: WatchOptions::default(),
         }
     }
 
-    // internal function to load sources
-    fn load_sources(self) -> Result<ConfigMap, ConfigError> {
-        let mut cfg_map = HashMap::new();
+    // internal function returning the sources in the order they are applied,
+    // lowest priority first and, within the same priority, in insertion order
+    fn ordered_sources(&self) -> Vec<ConfigSource> {
+        let mut sources = self.sources.clone();
+        sources.sort_by_key(|(_, priority)| *priority);
+        sources.into_iter().map(|(src, _)| src).collect()
+    }
 
-        for (_, mut src) in self.sources.into_iter().enumerate() {
-            match src.0 {
-                ConfigSource::File(path) => match path.file_type() {
-                    FileType::Json => {
-                        let reader = JsonConfigReader;
-                        src.1 = reader.read(path.to_string().as_str())?;
-                        for (k, v) in src.1.into_iter() {
-                            cfg_map.insert(k, v);
-                        }
-                    }
-                    FileType::Yaml => {
-                        let reader = YamlConfigReader;
-                        src.1 = reader.read(&path.to_string())?;
-                        for (k, v) in src.1.into_iter() {
-                            cfg_map.insert(k, v);
-                        }
-                    }
-                    FileType::Unsupported(path) => {
-                        return Err(ConfigError::FileReadError(path, "Unsupported".to_string()))
-                    }
+    // internal function returning the selected profile, if any
+    fn active_profile(&self) -> Option<String> {
+        self.profile.clone().or_else(|| {
+            let var = self.profile_env.as_ref()?;
+            std::env::var(var).ok().filter(|p| !p.is_empty())
+        })
+    }
+
+    // internal function to read a file source along with the files it extends and includes,
+    // overlaid with the sections and sibling file of `profile`. Sets `found` if the profile
+    // matched anything. Profile sections are only taken out of files when profiles are in use.
+    fn read_file_source(
+        &self,
+        path: &FilePath,
+        profile: Option<&str>,
+        found: &mut bool,
+    ) -> Result<Vec<Layer>, ConfigError> {
+        let mut paths = vec![path.clone()];
+        if let Some(profile) = profile {
+            let sibling = profile_path(path, profile);
+            if std::path::Path::new(sibling.as_ref()).is_file() {
+                *found = true;
+                paths.push(sibling);
+            }
+        }
+
+        let mut layers = Vec::new();
+        for path in &paths {
+            for mut layer in read_with_includes(path)? {
+                let section = if self.profile.is_some() || self.profile_env.is_some() {
+                    take_profile_section(&mut layer, profile)
+                } else {
+                    None
+                };
+                *found |= section.is_some();
+                layers.push(layer);
+                layers.extend(section);
+            }
+        }
+        Ok(layers)
+    }
+
+    // internal function rewriting aliased flags, e.g. `--port=80` to `--server.port=80`
+    fn resolve_flags(&self, args: &[String]) -> Vec<String> {
+        let mut resolved = Vec::with_capacity(args.len());
+        let mut args = args.iter();
+        for arg in args.by_ref() {
+            if arg == "--" {
+                resolved.push(arg.clone());
+                break;
+            }
+            let Some(flag) = arg.strip_prefix("--") else {
+                resolved.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (flag, None),
+            };
+            let alias = |name: &str| {
+                self.cli_aliases
+                    .iter()
+                    .find(|(alias, _)| alias == name)
+                    .map(|(_, key)| key)
+            };
+            resolved.push(match (alias(name), name.strip_prefix("no-")) {
+                (Some(key), _) => match value {
+                    Some(value) => format!("--{}={}", key, value),
+                    None => format!("--{}", key),
                 },
-                _ => unimplemented!(),
+                (None, Some(negated)) if value.is_none() => match alias(negated) {
+                    Some(key) => format!("--no-{}", key),
+                    None => arg.clone(),
+                },
+                _ => arg.clone(),
+            });
+        }
+        resolved.extend(args.cloned());
+        resolved
+    }
+
+    // internal function to read a single source, along with the location of its keys. File
+    // sources are read along with the files they extend and include, each as its own source.
+    fn read_source(
+        &self,
+        src: &ConfigSource,
+        profile: Option<&str>,
+        found: &mut bool,
+    ) -> Result<Vec<Layer>, ConfigError> {
+        let cfg_map = match src {
+            ConfigSource::File(path) => return self.read_file_source(path, profile, found),
+            ConfigSource::Environment => self.env.read()?,
+            ConfigSource::CommandLine(args) => self.cli.read(&self.resolve_flags(args))?,
+        };
+        Ok(vec![Layer {
+            source: src.clone(),
+            cfg_map,
+            locations: HashMap::new(),
+        }])
+    }
+
+    // internal function to load sources, deep merging each one over the previous ones. Also
+    // returns every file that was read.
+    fn load_sources(&self) -> Result<(ConfigMap, ProvenanceTracker, Vec<FilePath>), ConfigError> {
+        let mut cfg_map = self.defaults.clone();
+        let mut provenance = ProvenanceTracker::default();
+        let mut files = Vec::new();
+        let profile = self.active_profile();
+        let mut found = false;
+
+        for src in self.ordered_sources() {
+            for layer in self.read_source(&src, profile.as_deref(), &mut found)? {
+                if let ConfigSource::File(path) = &layer.source {
+                    if !files.contains(path) {
+                        files.push(path.clone());
+                    }
+                }
+                provenance.record(&layer.source, &layer.cfg_map, &layer.locations);
+                merge_maps(&mut cfg_map, layer.cfg_map, &self.array_merge);
             }
         }
+        if let (Some(profile), false) = (profile, found) {
+            return Err(ConfigError::ProfileNotFoundError(profile));
+        }
+
+        if self.interpolate {
+            // Environment variables were already expanded by whoever set them.
+            let literal = |path: &str| {
+                KeyPath::parse(path)
+                    .ok()
+                    .and_then(|path| provenance.get_nearest(&path.to_pointer()))
+                    .is_some_and(|p| p.source == ConfigSource::Environment)
+            };
+            cfg_map = interpolate(&cfg_map, |var| std::env::var(var).ok(), literal)?;
+        }
+        Ok((cfg_map, provenance, files))
+    }
+
+    // internal function to create the manager from the loaded sources and validate it
+    #[cfg_attr(not(feature = "watch"), allow(unused_variables))]
+    fn finish(
+        self,
+        (cfgs, provenance, files): (ConfigMap, ProvenanceTracker, Vec<FilePath>),
+    ) -> Result<ConfigManager, ConfigError> {
+        let mut cm = ConfigManager::new(cfgs, self.ordered_sources());
+        cm.provenance = provenance;
+        #[cfg(feature = "watch")]
+        {
+            cm.files = files;
+        }
 
-        Ok(cfg_map)
+        #[cfg_attr(not(feature = "schema"), allow(unused_mut))]
+        let mut expected = self.known_keys.clone();
+        #[cfg(feature = "schema")]
+        let schemas = self
+            .schemas
+            .iter()
+            .map(Schema::load)
+            .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(feature = "schema")]
+        for schema in &schemas {
+            let declared = Expected::from_schema(schema);
+            expected = Some(match expected {
+                Some(expected) => expected.union(declared),
+                None => declared,
+            });
+        }
+        if self.strict {
+            let expected = expected.ok_or_else(|| {
+                ConfigError::ValidationError(
+                    "Strict mode needs a schema or known keys to check keys against".to_string(),
+                )
+            })?;
+            check_unknown_keys(&cm, &expected)?;
+        }
+        #[cfg(feature = "schema")]
+        for schema in &schemas {
+            schema::validate(schema, &cm)?;
+        }
+
+        for validator in &self.validators {
+            (validator.0)(&cm)?;
+        }
+        cm.builder = self;
+        Ok(cm)
     }
 
     /// Add a new source of configuration to [`ConfigManager`]
     ///
+    /// Sources are applied in the order they are added, so values from a later source override
+    /// values from an earlier one. Adding a source that was already added moves it to the end.
+    ///
+    /// A file source may be composed of other files through the reserved top-level keys
+    /// `$extends: base.yaml` and `$include: [common.yaml, db/*.yaml]`, resolved relative to the
+    /// file. The extended file, then the included ones in order (glob matches sorted by path),
+    /// are merged before the file itself, so the file overrides them. Includes nest; an include
+    /// cycle fails the build with [`ConfigError::IncludeError`].
+    ///
     /// # Arguments
     ///
     /// * `src` - Configuration source to be added
@@ -98,7 +338,489 @@ impl ConfigManagerBuilder {
     /// builder.add_source(ConfigSource::File(FilePath::new("config.json")));
     /// ```
     pub fn add_source(&mut self, src: ConfigSource) -> &mut Self {
-        self.sources.insert(src, HashMap::new());
+        self.add_source_with_priority(src, Self::DEFAULT_PRIORITY)
+    }
+
+    /// Add a new source of configuration with an explicit priority.
+    ///
+    /// Sources with a higher priority override sources with a lower one, regardless of the
+    /// order they were added in. Sources sharing a priority are applied in insertion order.
+    /// [`ConfigManagerBuilder::add_source`] uses [`ConfigManagerBuilder::DEFAULT_PRIORITY`].
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Configuration source to be added
+    /// * `priority` - Precedence of the source
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource, FilePath};
+    ///
+    /// let args = std::env::args().collect();
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder
+    ///     .add_source_with_priority(ConfigSource::CommandLine(args), 30)
+    ///     .add_source_with_priority(ConfigSource::Environment, 20)
+    ///     .add_source_with_priority(ConfigSource::File(FilePath::new("prod.yaml")), 10)
+    ///     .add_source(ConfigSource::File(FilePath::new("defaults.yaml")));
+    /// ```
+    pub fn add_source_with_priority(&mut self, src: ConfigSource, priority: i32) -> &mut Self {
+        self.sources.retain(|(existing, _)| *existing != src);
+        self.sources.push((src, priority));
+        self
+    }
+
+    /// Sets the prefix environment variables must start with to be read by
+    /// [`ConfigSource::Environment`]. The prefix is stripped from the resulting keys.
+    ///
+    /// Without a prefix, only the variables given to [`ConfigManagerBuilder::env_alias`] are read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// // MYAPP_DATABASE__URL -> database.url
+    /// builder.env_prefix("MYAPP_").add_source(ConfigSource::Environment);
+    /// ```
+    pub fn env_prefix<S: AsRef<str>>(&mut self, prefix: S) -> &mut Self {
+        self.env = self.env.clone().with_prefix(prefix);
+        self
+    }
+
+    /// Sets the separator used to split environment variable names into nested keys.
+    /// Defaults to `__`.
+    pub fn env_separator<S: AsRef<str>>(&mut self, separator: S) -> &mut Self {
+        self.env = self.env.clone().with_separator(separator);
+        self
+    }
+
+    /// Reads the environment variable `var` into the dotted `key` when
+    /// [`ConfigSource::Environment`] is read, whether or not `var` starts with the prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// // DATABASE_URL -> database.url
+    /// builder
+    ///     .env_prefix("MYAPP_")
+    ///     .env_alias("DATABASE_URL", "database.url")
+    ///     .add_source(ConfigSource::Environment);
+    /// ```
+    pub fn env_alias<S: AsRef<str>, K: AsRef<str>>(&mut self, var: S, key: K) -> &mut Self {
+        self.env = self.env.clone().with_alias(var, key);
+        self
+    }
+
+    /// Makes [`ConfigSource::Environment`] read only the variables given to
+    /// [`ConfigManagerBuilder::env_alias`], rather than every variable starting with the prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// // Only DATABASE_URL -> database.url
+    /// builder
+    ///     .env_aliases_only()
+    ///     .env_alias("DATABASE_URL", "database.url")
+    ///     .add_source(ConfigSource::Environment);
+    /// ```
+    pub fn env_aliases_only(&mut self) -> &mut Self {
+        self.env = self.env.clone().with_aliases_only();
+        self
+    }
+
+    /// Lets `--key value` set the dotted `key` from the argument that follows it when a
+    /// [`ConfigSource::CommandLine`] is read. Other keys are only set by `--key=value`, and a
+    /// bare `--key` sets `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let args = ["--port", "8080", "--verbose", "input.txt"].map(String::from).to_vec();
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder
+    ///     .cli_value_flag("port")
+    ///     .add_source(ConfigSource::CommandLine(args));
+    /// let config_manager = builder.build().unwrap();
+    /// assert_eq!(config_manager.get_i64("port"), Some(8080));
+    /// assert_eq!(config_manager.get_bool("verbose"), Some(true));
+    /// ```
+    pub fn cli_value_flag<S: AsRef<str>>(&mut self, key: S) -> &mut Self {
+        self.cli = self.cli.clone().with_value_flag(key);
+        self
+    }
+
+    /// Reads the command-line flag `--flag` (and `--no-flag`) into the dotted `key` when a
+    /// [`ConfigSource::CommandLine`] is read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// // --port=8080 -> server.port
+    /// builder
+    ///     .cli_alias("port", "server.port")
+    ///     .add_source(ConfigSource::CommandLine(vec!["--port=8080".to_string()]));
+    /// let config_manager = builder.build().unwrap();
+    /// assert_eq!(config_manager.get_i64("server.port"), Some(8080));
+    /// ```
+    pub fn cli_alias<S: Into<String>, K: Into<String>>(&mut self, flag: S, key: K) -> &mut Self {
+        let flag = flag.into();
+        self.cli_aliases.retain(|(existing, _)| *existing != flag);
+        self.cli_aliases.push((flag, key.into()));
+        self
+    }
+
+    /// Sets the value of the dotted `key`, such as `server.port`, when no source supplies it.
+    ///
+    /// Defaults are merged before every source, so objects and arrays are combined with the
+    /// values of the sources like any other layer. They have no [`Provenance`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder
+    ///     .set_default("server.port", 8080)
+    ///     .add_source(ConfigSource::CommandLine(vec![]));
+    /// let config_manager = builder.build().unwrap();
+    /// assert_eq!(config_manager.get_i64("server.port"), Some(8080));
+    /// ```
+    pub fn set_default<K: AsRef<str>, V: Into<Value>>(&mut self, key: K, value: V) -> &mut Self {
+        let path = key
+            .as_ref()
+            .split('.')
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        insert_path(&mut self.defaults, &path, value.into());
+        self
+    }
+
+    /// Sets how arrays set by several sources are combined. Defaults to
+    /// [`ArrayMergeStrategy::Replace`]. Nested objects are always merged key by key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ArrayMergeStrategy, ConfigManagerBuilder};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.array_merge_strategy(ArrayMergeStrategy::Append);
+    /// ```
+    pub fn array_merge_strategy(&mut self, strategy: ArrayMergeStrategy) -> &mut Self {
+        self.array_merge = strategy;
+        self
+    }
+
+    /// Adds a check the merged configuration must pass. Validators run on every build and on
+    /// every reload, so a configuration that fails them is never handed out.
+    ///
+    /// # Arguments
+    ///
+    /// * `validator` - Returns [`Err`] if the configuration is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{error::ConfigError, ConfigManagerBuilder};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.add_validator(|cm| match cm.get_u64("workers") {
+    ///     Some(0) => Err(ConfigError::ValidationError("workers must be positive".to_string())),
+    ///     _ => Ok(()),
+    /// });
+    /// ```
+    pub fn add_validator<F>(&mut self, validator: F) -> &mut Self
+    where
+        F: Fn(&ConfigManager) -> Result<(), ConfigError> + Send + Sync + 'static,
+    {
+        self.validators.push(Validator(Arc::new(validator)));
+        self
+    }
+
+    /// Adds a validator requiring the merged configuration to parse into `T`, see
+    /// [`ConfigManager::parse`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, Deserialize};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct AppConfig {
+    ///     port: u16,
+    /// }
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.validate_as::<AppConfig>();
+    /// ```
+    pub fn validate_as<T>(&mut self) -> &mut Self
+    where
+        T: DeserializeOwned,
+    {
+        self.add_validator(|cm| cm.parse::<T>().map(|_| ()))
+    }
+
+    #[cfg(feature = "schema")]
+    /// Adds a validator requiring the merged configuration to match the JSON Schema (draft
+    /// 2020-12) `schema`.
+    ///
+    /// Every violation is reported in a single [`ConfigError::ValidationError`], each with the
+    /// path of the offending value and the source that supplied it, e.g.
+    /// `server.port: "abc" is not of type "integer" (from config.yaml:3:9)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::ConfigManagerBuilder;
+    /// use serde_json::json;
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.schema(json!({
+    ///     "type": "object",
+    ///     "properties": { "port": { "type": "integer", "maximum": 65535 } },
+    ///     "required": ["port"]
+    /// }));
+    /// ```
+    pub fn schema(&mut self, schema: Value) -> &mut Self {
+        self.schemas.push(Schema::Inline(schema));
+        self
+    }
+
+    #[cfg(feature = "schemars")]
+    /// Adds a validator requiring the merged configuration to match the JSON Schema generated
+    /// for `T` by [`schema_for`](crate::schema::schema_for). See [`ConfigManagerBuilder::schema`].
+    ///
+    /// Unlike [`ConfigManagerBuilder::validate_as`], every mismatch is reported at once, along
+    /// with the sources that supplied the offending values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, Deserialize, JsonSchema};
+    ///
+    /// #[derive(Deserialize, JsonSchema)]
+    /// struct AppConfig {
+    ///     port: u16,
+    /// }
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.schema_for::<AppConfig>();
+    /// ```
+    pub fn schema_for<T: schemars::JsonSchema>(&mut self) -> &mut Self {
+        self.schema(crate::schema::schema_for::<T>())
+    }
+
+    #[cfg(feature = "schema")]
+    /// Adds a validator requiring the merged configuration to match the JSON Schema read from
+    /// `path`, written in JSON, YAML or TOML. See [`ConfigManagerBuilder::schema`].
+    ///
+    /// The schema is read on every build and reload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, FilePath};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.schema_file(FilePath::new("config.schema.json"));
+    /// ```
+    pub fn schema_file(&mut self, path: FilePath) -> &mut Self {
+        self.schemas.push(Schema::File(path));
+        self
+    }
+
+    /// Declares dotted keys, such as `database.url`, the configuration may contain. Anything
+    /// may be nested under a declared key. Used by [`ConfigManagerBuilder::strict`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::ConfigManagerBuilder;
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.known_keys(["port", "database.url", "database.pool_size"]);
+    /// ```
+    pub fn known_keys<I, S>(&mut self, keys: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let declared = Expected::from_keys(keys);
+        self.known_keys = Some(match self.known_keys.take() {
+            Some(known) => known.union(declared),
+            None => declared,
+        });
+        self
+    }
+
+    /// Enables or disables strict mode, which is disabled by default.
+    ///
+    /// In strict mode, building fails with [`ConfigError::ValidationError`] if the configuration
+    /// contains keys that are neither declared with [`ConfigManagerBuilder::known_keys`] nor
+    /// described by the `properties` of a schema (with the `schema` feature), which catches
+    /// typos that would otherwise be ignored while defaults silently apply. Every unknown key is
+    /// reported with its source and the closest expected key, e.g.
+    /// `databse_url (from config.yaml:3:1), did you mean `database_url`?`.
+    ///
+    /// Keys supplied by [`ConfigSource::Environment`] are not checked, since the environment is
+    /// shared with other programs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder
+    ///     .known_keys(["database_url"])
+    ///     .strict(true)
+    ///     .add_source(ConfigSource::CommandLine(vec!["--databse_url=x".to_string()]));
+    /// let error = builder.build().unwrap_err();
+    /// assert!(error.to_string().contains("did you mean `database_url`?"));
+    /// ```
+    pub fn strict(&mut self, enabled: bool) -> &mut Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Enables or disables the expansion of references inside string values, which is disabled
+    /// by default so that existing `${...}` text is kept as it is.
+    ///
+    /// Once all sources are merged, references in string values are expanded:
+    ///
+    /// * `${other.key}` is replaced by the value at `other.key` (a key or [`KeyPath`]), which
+    ///   may contain references itself
+    /// * `${ENV:NAME}` and `${env.NAME}` are replaced by the environment variable `NAME`
+    /// * `${...:-default}` falls back to `default` if the key or variable doesn't exist
+    /// * `$${` is an escaped, literal `${`
+    ///
+    /// A value consisting of a single reference takes the type of the referenced value, so
+    /// `${database.port}` stays a number. Values supplied by [`ConfigSource::Environment`] are
+    /// taken literally.
+    ///
+    /// Building fails with [`ConfigError::InterpolationError`] if a reference is undefined or
+    /// references form a cycle; the error names the chain of keys involved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::ConfigManagerBuilder;
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.interpolation(true);
+    /// ```
+    pub fn interpolation(&mut self, enabled: bool) -> &mut Self {
+        self.interpolate = enabled;
+        self
+    }
+
+    /// Selects the profile, such as `dev` or `prod`, whose overrides are layered over each file
+    /// source.
+    ///
+    /// For a file source `config.yaml`, the profile's overrides are taken from:
+    ///
+    /// * the `profiles.prod` section of the file, merged over the rest of it
+    /// * the sibling file `config.prod.yaml`, if it exists, merged over `config.yaml`
+    ///
+    /// Once profiles are in use, through this method or [`ConfigManagerBuilder::profile_env`],
+    /// a top-level `profiles` map is reserved for profile sections and removed from every file,
+    /// whether a profile is selected or not. A `profiles` key holding anything other than a map
+    /// is left in the configuration. Building fails with [`ConfigError::ProfileNotFoundError`] if no file
+    /// has a section or sibling file for the selected profile.
+    ///
+    /// Takes precedence over [`ConfigManagerBuilder::profile_env`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource, FilePath};
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder
+    ///     .add_source(ConfigSource::File(FilePath::new("config.yaml")))
+    ///     .profile("prod");
+    /// ```
+    pub fn profile<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    /// Selects the profile from the environment variable `var`, such as `APP_PROFILE`, when no
+    /// profile was set with [`ConfigManagerBuilder::profile`]. No profile is selected if the
+    /// variable is unset or empty.
+    ///
+    /// The variable is read on every build and reload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::ConfigManagerBuilder;
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.profile_env("APP_PROFILE");
+    /// ```
+    pub fn profile_env<S: Into<String>>(&mut self, var: S) -> &mut Self {
+        self.profile_env = Some(var.into());
+        self
+    }
+
+    #[cfg(feature = "watch")]
+    /// Coalesces bursts of file change events into a single notification.
+    ///
+    /// Editors and tools like `kubectl` emit several create, modify and rename events for one
+    /// save. With a debounce window, [`ConfigManager::watch_file_changes`] holds events back
+    /// until none arrived for the whole window and then emits them as one [`FileChangeEvent`],
+    /// so [`ConfigManager::watch_and_reload`] reloads once per save.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rustic_config::ConfigManagerBuilder;
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.watch_debounce(Duration::from_millis(200));
+    /// ```
+    pub fn watch_debounce(&mut self, window: Duration) -> &mut Self {
+        self.watch.debounce = Some(window);
+        self
+    }
+
+    #[cfg(feature = "watch")]
+    /// Watches files by polling them every `interval` instead of using native file system
+    /// notifications.
+    ///
+    /// Native notifications never fire on some network, FUSE and overlay filesystems. Polling
+    /// compares file contents, so changes are noticed even when modification times aren't
+    /// updated. Polling is also used automatically, every two seconds, when the native watcher
+    /// fails to initialise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rustic_config::ConfigManagerBuilder;
+    ///
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.watch_poll_interval(Duration::from_secs(5));
+    /// ```
+    pub fn watch_poll_interval(&mut self, interval: Duration) -> &mut Self {
+        self.watch.poll_interval = Some(interval);
         self
     }
 
@@ -106,7 +828,8 @@ impl ConfigManagerBuilder {
     ///
     /// # Errors
     ///
-    /// Returns [`Err`] if no sources have been added or if there's an issue loading the configuration.
+    /// Returns [`Err`] if no sources have been added, if there's an issue loading the configuration
+    /// or if it fails one of the validators.
     ///
     /// # Examples
     ///
@@ -121,15 +844,46 @@ impl ConfigManagerBuilder {
         if self.sources.is_empty() {
             return Err(ConfigError::EmptySources);
         }
-        let srcs = self.sources.keys().cloned().collect::<Vec<ConfigSource>>();
-        let cfgs = self.load_sources()?;
-        // let new_srcs: Vec<ConfigSource> = srcs.iter().cloned().collect();
-        Ok(ConfigManager::new(cfgs, srcs))
+        let loaded = self.load_sources()?;
+        self.finish(loaded)
+    }
+
+    #[cfg(feature = "async")]
+    /// Builds the [`ConfigManager`] like [`ConfigManagerBuilder::build`], on tokio's blocking
+    /// thread pool so that reading files, including extended, included, globbed and profile
+    /// files, doesn't block the runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`ConfigManagerBuilder::build`], or
+    /// [`ConfigError::BuildCancelled`] if the runtime shuts down before the build completes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustic_config::{ConfigManagerBuilder, ConfigSource, FilePath};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let mut builder = ConfigManagerBuilder::new();
+    /// builder.add_source(ConfigSource::File(FilePath::new("test/test.json")));
+    /// let config_manager = builder.build_async().await.unwrap();
+    /// # });
+    /// ```
+    pub async fn build_async(self) -> Result<ConfigManager, ConfigError> {
+        match tokio::task::spawn_blocking(move || self.build()).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(ConfigError::BuildCancelled(e.to_string())),
+        }
     }
 }
 
 /// ConfigManager holds and manages the application's configuration.
 ///
+/// Every getter accepts either a top-level key or a [`KeyPath`] to a nested value, written as
+/// `database.replicas[2].host` or as the JSON Pointer `/database/replicas/2/host`. A top-level
+/// key that matches exactly takes precedence over interpreting it as a path.
+///
 /// # Examples
 ///
 /// ```should_panic
@@ -144,7 +898,13 @@ impl ConfigManagerBuilder {
 #[derive(Debug)]
 pub struct ConfigManager {
     configs: ConfigMap,
-    sources: Vec<ConfigSource>,
+    provenance: ProvenanceTracker,
+    builder: ConfigManagerBuilder,
+    #[cfg(feature = "watch")]
+    pub(crate) subscriptions: Subscriptions,
+    // Every file the configuration was read from, including extended and included ones.
+    #[cfg(feature = "watch")]
+    files: Vec<FilePath>,
 }
 
 impl ConfigManager {
@@ -153,6 +913,7 @@ impl ConfigManager {
     /// # Arguments
     ///
     /// * `configs` - A map holding the configuration key-value pairs.
+    /// * `sources` - The sources the configuration was loaded from.
     ///
     /// # Examples
     ///
@@ -160,17 +921,63 @@ impl ConfigManager {
     /// use rustic_config::{ConfigManager, ConfigMap};
     ///
     /// let configs = ConfigMap::new();
-    /// let config_manager = ConfigManager::new(configs);
+    /// let config_manager = ConfigManager::new(configs, vec![]);
     /// ```
     pub fn new(configs: ConfigMap, sources: Vec<ConfigSource>) -> Self {
-        Self { configs, sources }
+        let mut builder = ConfigManagerBuilder::new();
+        for src in &sources {
+            builder.add_source(src.clone());
+        }
+        Self {
+            configs,
+            provenance: ProvenanceTracker::default(),
+            builder,
+            #[cfg(feature = "watch")]
+            subscriptions: Subscriptions::default(),
+            #[cfg(feature = "watch")]
+            files: Vec::new(),
+        }
+    }
+
+    /// Returns where the value at `key` came from: the source that supplied it, its location
+    /// for file sources, and the lower-priority values it overrode.
+    ///
+    /// For an element of an array, the provenance of the whole array is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
+    ///
+    /// # Returns
+    ///
+    /// Returns [`Some(&Provenance)`] if the key was supplied by one of the sources; otherwise [`None`].
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // assuming "database.url" is set in base.yaml and overridden by prod.json
+    /// let provenance = config_manager.source_of("database.url").unwrap();
+    /// assert_eq!(provenance.source, ConfigSource::File(FilePath::new("prod.json")));
+    /// assert_eq!(provenance.location.unwrap().line, 3);
+    /// assert_eq!(provenance.overridden.len(), 1);
+    /// ```
+    pub fn source_of(&self, key: &str) -> Option<&Provenance> {
+        // An exact top-level key wins over a path, as in lookups.
+        if let Some(provenance) = self.provenance.get(&pointer_join("", key)) {
+            return Some(provenance);
+        }
+        KeyPath::parse(key)
+            .ok()?
+            .normalized(&self.configs)
+            .ancestors()
+            .find_map(|path| self.provenance.get(&path.to_pointer()))
     }
 
     /// Fetches a [`String`] value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -184,16 +991,14 @@ impl ConfigManager {
     /// assert_eq!(site_name, "MySite");
     /// ```
     pub fn get_string(&self, key: &str) -> Option<String> {
-        self.configs
-            .get(key)
-            .and_then(|v| v.as_str().map(String::from))
+        self.lookup(key).and_then(|v| v.as_str().map(String::from))
     }
 
     /// Fetches a string slice [`&str`] value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -207,14 +1012,14 @@ impl ConfigManager {
     /// assert_eq!(api_endpoint, "http://example.com/api");
     /// ```
     pub fn get_str(&self, key: &str) -> Option<&str> {
-        self.configs.get(key).and_then(|v| v.as_str())
+        self.lookup(key).and_then(|v| v.as_str())
     }
 
     /// Fetches a boolean ([`bool`]) value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -228,14 +1033,14 @@ impl ConfigManager {
     /// assert!(feature_enabled);
     /// ```
     pub fn get_bool(&self, key: &str) -> Option<bool> {
-        self.configs.get(key).and_then(|v| v.as_bool())
+        self.lookup(key).and_then(|v| v.as_bool())
     }
 
     /// Fetches an [`i64`] value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -249,14 +1054,14 @@ impl ConfigManager {
     /// assert_eq!(max_connections, 100);
     /// ```
     pub fn get_i64(&self, key: &str) -> Option<i64> {
-        self.configs.get(key).and_then(|v| v.as_i64())
+        self.lookup(key).and_then(|v| v.as_i64())
     }
 
     /// Fetches an [`f64`] value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -270,14 +1075,14 @@ impl ConfigManager {
     /// assert_eq!(discount_rate, 0.15);
     /// ```
     pub fn get_f64(&self, key: &str) -> Option<f64> {
-        self.configs.get(key).and_then(|v| v.as_f64())
+        self.lookup(key).and_then(|v| v.as_f64())
     }
 
     /// Fetches a [`u64`] value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -291,14 +1096,14 @@ impl ConfigManager {
     /// assert_eq!(user_count, 5000);
     /// ```
     pub fn get_u64(&self, key: &str) -> Option<u64> {
-        self.configs.get(key).and_then(|v| v.as_u64())
+        self.lookup(key).and_then(|v| v.as_u64())
     }
 
     /// Fetches a [`serde_json::value::Number`] value from the configuration.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -312,7 +1117,7 @@ impl ConfigManager {
     /// assert_eq!(pi, &serde_json::value::Number::from_f64(3.14159).unwrap());
     /// ```
     pub fn get_number(&self, key: &str) -> Option<&Number> {
-        self.configs.get(key).and_then(|v| v.as_number())
+        self.lookup(key).and_then(|v| v.as_number())
     }
 
     /// Fetches a vector of `T` values from the configuration.
@@ -323,7 +1128,7 @@ impl ConfigManager {
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -340,10 +1145,9 @@ impl ConfigManager {
     where
         T: DeserializeOwned + Serialize + Send + Sync + 'static,
     {
-        self.configs.get(key).and_then(|v| {
-            v.as_array()
-                .unwrap()
-                .into_iter()
+        self.lookup(key).and_then(|v| {
+            v.as_array()?
+                .iter()
                 .map(|item| serde_json::from_value(item.clone()).ok())
                 .collect::<Option<Vec<T>>>()
         })
@@ -353,7 +1157,7 @@ impl ConfigManager {
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -367,18 +1171,15 @@ impl ConfigManager {
     /// assert_eq!(*timeout, 30.into());
     /// ```
     pub fn try_get(&self, key: &str) -> Result<&Value, ConfigError> {
-        if let Some(cfg) = self.configs.get(key) {
-            Ok(cfg)
-        } else {
-            return Err(ConfigError::NullValue(key.to_owned()));
-        }
+        self.lookup(key)
+            .ok_or_else(|| ConfigError::NullValue(key.to_owned()))
     }
 
     /// Fetches the associated Map if the value is a JSON Object.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -393,18 +1194,14 @@ impl ConfigManager {
     /// assert!(database_config.contains_key("password"));
     /// ```
     pub fn get_object(&self, key: &str) -> Option<&Map<String, Value>> {
-        if let Some(v) = self.configs.get(key) {
-            return v.as_object();
-        } else {
-            return None;
-        }
+        self.lookup(key).and_then(|v| v.as_object())
     }
 
     /// Returns a mutable reference to the value corresponding to the key.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value.
+    /// * `key` - The key or [`KeyPath`] for the configuration value.
     ///
     /// # Returns
     ///
@@ -420,10 +1217,12 @@ impl ConfigManager {
     /// assert_eq!(config_manager.get_i64("counter").unwrap(), 1);
     /// ```
     pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
-        self.configs.get_mut(key)
+        self.lookup_mut(key)
     }
 
-    /// Returns the key-value pair corresponding to the supplied key.
+    /// Returns the key-value pair corresponding to the supplied top-level key.
+    ///
+    /// Unlike the getters, `key` is not interpreted as a [`KeyPath`].
     pub fn get_key_value(&mut self, key: &str) -> Option<(&String, &Value)> {
         self.configs.get_key_value(key)
     }
@@ -458,8 +1257,7 @@ impl ConfigManager {
     where
         T: DeserializeOwned,
     {
-        self.configs
-            .get(key)
+        self.lookup(key)
             .ok_or_else(|| ConfigError::KeyNotFoundError(key.to_owned())) // Create this error variant if it doesn't exist
             .and_then(|v| {
                 serde_json::from_value(v.clone())
@@ -496,11 +1294,11 @@ impl ConfigManager {
     ///
     /// # Arguments
     ///
-    /// * `key` - The key for the configuration value to remove.
+    /// * `key` - The key or [`KeyPath`] for the configuration value to remove.
     ///
     /// # Returns
     ///
-    /// Returns the removed `Value` if the key exists; otherwise, it leaves a Null in its place.
+    /// Returns the removed `Value` if the key exists, leaving a Null in its place; otherwise a Null.
     ///
     /// # Examples
     ///
@@ -510,12 +1308,27 @@ impl ConfigManager {
     /// assert_eq!(removed_value, serde_json::Value::Null);
     /// ```
     pub fn take(&mut self, key: &str) -> Value {
-        self.configs.get_mut(key).unwrap().take()
+        self.lookup_mut(key).map(Value::take).unwrap_or(Value::Null)
     }
 
     #[cfg(feature = "watch")]
     /// Watch for configuration file changes
     ///
+    /// Every [`ConfigSource::File`] source is watched; other sources are skipped. Each event is
+    /// tagged with the files it concerns.
+    ///
+    /// Files are watched through their parent directory and the directory their symlinks
+    /// resolve to, so files replaced by a rename or a symlink swap (as Kubernetes does for
+    /// mounted ConfigMaps and Secrets) keep being watched.
+    ///
+    /// Errors occurring while watching are delivered on the returned receiver. Watching stops
+    /// when `term_rx` receives the shutdown signal or the receiver is dropped; the returned
+    /// [`WatchHandle`] can be joined to wait for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::FileWatchError`] if a file doesn't exist or can't be watched.
+    ///
     /// # Examples
     ///
     /// ```should_panic
@@ -530,11 +1343,14 @@ impl ConfigManager {
     ///     .build()?;
     ///
     /// let (term_tx, term_rx) = oneshot::channel();
-    /// let rx = config_manager.watch_file_changes(term_rx)?;
+    /// let (rx, handle) = config_manager.watch_file_changes(term_rx)?;
     ///
     /// thread::spawn(move || {
     ///     for event in rx {
-    ///         println!("File Watcher Event: {:?}", event.unwrap())
+    ///         match event {
+    ///             Ok(event) => println!("File Watcher Event: {:?}", event.paths),
+    ///             Err(e) => println!("File Watcher Error: {}", e),
+    ///         }
     ///     }
     /// });
     ///
@@ -542,50 +1358,257 @@ impl ConfigManager {
     /// thread::sleep(Duration::from_secs(10));
     /// // When you want to stop watching the file
     /// term_tx.send(()).unwrap();
+    /// handle.join()?;
     ///
     /// # Ok::<(), ConfigError>(())
     /// ```
     pub fn watch_file_changes(
         &self,
         term_rx: oneshot::Receiver<()>,
-    ) -> Result<Receiver<NotifyResult<Event>>, ConfigError> {
-        use std::{path::Path, thread};
+    ) -> Result<(Receiver<NotifyResult<FileChangeEvent>>, WatchHandle), ConfigError> {
+        let (tx, rx) = channel();
+        let handle = spawn_watcher(
+            self.file_sources(),
+            self.builder.watch.clone(),
+            term_rx,
+            move |res| tx.send(res).is_ok(),
+        )?;
+        Ok((rx, handle))
+    }
+
+    #[cfg(feature = "watch")]
+    /// Watch for configuration file changes and reload the configuration when they happen.
+    ///
+    /// The manager is moved into a [`SharedConfig`]. On every change all sources are re-read and
+    /// merged into a new configuration, which is then swapped in with
+    /// [`SharedConfig::reload`], so readers always see a consistent snapshot. If reloading
+    /// fails, because a file can't be parsed or the new configuration doesn't pass the validators
+    /// added with [`ConfigManagerBuilder::add_validator`], the last known-good configuration is
+    /// kept and the error is reported on the returned receiver. Subscribers registered with
+    /// [`ConfigManager::subscribe`] are notified after each successful reload.
+    ///
+    /// The returned receiver yields the outcome of every reload attempt. Dropping it doesn't stop
+    /// the reloads; watching only stops once the terminator is sent or dropped, and the returned
+    /// [`WatchHandle`] waits for it to stop.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::FileWatchError`] if a file doesn't exist or can't be watched.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use rustic_config::{ConfigManagerBuilder, FilePath, ConfigSource, error::ConfigError};
+    ///
+    /// let mut config_manager_builder = ConfigManagerBuilder::new();
+    /// config_manager_builder.add_source(ConfigSource::File(FilePath::new("config.yaml")));
+    /// let config_manager = config_manager_builder.build()?;
+    ///
+    /// let (term_tx, term_rx) = oneshot::channel();
+    /// let (shared, reloads, handle) = config_manager.watch_and_reload(term_rx)?;
+    ///
+    /// for outcome in reloads.iter().take(10) {
+    ///     match outcome {
+    ///         Ok(()) => println!("port is now {:?}", shared.snapshot().get_i64("port")),
+    ///         Err(e) => println!("Reload failed, keeping the current configuration: {}", e),
+    ///     }
+    /// }
+    /// term_tx.send(()).unwrap();
+    /// handle.join()?;
+    /// # Ok::<(), ConfigError>(())
+    /// ```
+    pub fn watch_and_reload(
+        self,
+        term_rx: oneshot::Receiver<()>,
+    ) -> Result<(SharedConfig, ReloadReceiver, WatchHandle), ConfigError> {
+        let (tx, rx) = channel();
+        self.spawn_reloader(term_rx, move |outcome| {
+            // Keep reloading even if nobody listens to the outcomes anymore.
+            let _ = tx.send(outcome);
+            true
+        })
+        .map(|(shared, handle)| (shared, rx, handle))
+    }
+
+    #[cfg(feature = "async")]
+    /// Watch for configuration file changes and reload the configuration when they happen,
+    /// reporting the outcomes as a [`Stream`](futures_core::Stream).
+    ///
+    /// Behaves like [`ConfigManager::watch_and_reload`], but instead of a terminator channel,
+    /// watching stops when the returned [`ReloadStream`] is dropped or
+    /// [stopped](ReloadStream::stop).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use rustic_config::{ConfigManagerBuilder, FilePath, ConfigSource, error::ConfigError};
+    ///
+    /// # async fn run() -> Result<(), ConfigError> {
+    /// let mut config_manager_builder = ConfigManagerBuilder::new();
+    /// config_manager_builder.add_source(ConfigSource::File(FilePath::new("config.yaml")));
+    /// let config_manager = config_manager_builder.build_async().await?;
+    ///
+    /// let (shared, mut reloads) = config_manager.watch_and_reload_stream()?;
+    /// while let Some(outcome) = reloads.next().await {
+    ///     match outcome {
+    ///         Ok(()) => println!("port is now {:?}", shared.snapshot().get_i64("port")),
+    ///         Err(e) => println!("Reload failed, keeping the current configuration: {}", e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_and_reload_stream(self) -> Result<(SharedConfig, ReloadStream), ConfigError> {
+        let (term_tx, term_rx) = oneshot::channel();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.spawn_reloader(term_rx, move |outcome| tx.send(outcome).is_ok())
+            .map(|(shared, handle)| (shared, ReloadStream::new(rx, term_tx, handle)))
+    }
+
+    #[cfg(feature = "watch")]
+    // internal function moving the manager into a shared handle that is reloaded on every
+    // change of its files, handing each outcome to `on_outcome` for as long as it returns `true`
+    fn spawn_reloader<F>(
+        self,
+        term_rx: oneshot::Receiver<()>,
+        mut on_outcome: F,
+    ) -> Result<(SharedConfig, WatchHandle), ConfigError>
+    where
+        F: FnMut(Result<(), ConfigError>) -> bool + Send + 'static,
+    {
+        let files = self.file_sources();
+        let options = self.builder.watch.clone();
+        let shared = self.into_shared();
+        let reloading = shared.clone();
+
+        let handle = spawn_watcher(files, options, term_rx, move |res| {
+            on_outcome(match res {
+                Ok(event) if !event.is_content_change() => return true,
+                Ok(_) => reloading.reload(),
+                Err(e) => Err(ConfigError::FileWatchError(e.to_string())),
+            })
+        })?;
+        Ok((shared, handle))
+    }
+
+    /// Moves the manager into a [`SharedConfig`] handle, to share it across threads and reload
+    /// it in place.
+    pub fn into_shared(self) -> SharedConfig {
+        SharedConfig::new(self)
+    }
+
+    /// Re-reads and merges all sources the configuration was built from, returning the result
+    /// as a new [`ConfigManager`]. The current configuration is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] if there's an issue loading the configuration.
+    pub fn reload(&self) -> Result<ConfigManager, ConfigError> {
+        #[allow(unused_mut)]
+        let mut cm = self.builder.clone().build()?;
+        #[cfg(feature = "watch")]
+        {
+            cm.subscriptions = self.subscriptions.clone();
+        }
+        Ok(cm)
+    }
 
+    #[cfg(feature = "watch")]
+    /// Calls `callback` whenever the value at `key` changes across a reload, with both the old
+    /// and the new value. Reloads that leave the value untouched don't trigger it.
+    ///
+    /// Subscriptions are carried over to reloaded configurations, so they only fire for managers
+    /// reloaded through a [`SharedConfig`], e.g. by [`ConfigManager::watch_and_reload`]. The
+    /// callback runs on the reloading thread and must not reload that [`SharedConfig`] itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key or [`KeyPath`] to watch.
+    /// * `callback` - Called with the [`ValueChange`].
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let id = config_manager.subscribe("database.pool_size", |change| {
+    ///     println!("pool size changed from {} to {}", change.old, change.new);
+    /// });
+    /// ```
+    pub fn subscribe<F>(&self, key: &str, mut callback: F) -> SubscriptionId
+    where
+        F: FnMut(&ValueChange) + Send + 'static,
+    {
+        self.subscriptions.add(
+            key,
+            Box::new(move |change| {
+                callback(change);
+                true
+            }),
+        )
+    }
+
+    #[cfg(feature = "watch")]
+    /// Like [`ConfigManager::subscribe`], but delivers the changes on a channel. The subscription
+    /// ends when the receiver is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let pool_size_changes = config_manager.subscribe_channel("database.pool_size");
+    /// thread::spawn(move || {
+    ///     for change in pool_size_changes {
+    ///         pool.resize(change.new.as_u64().unwrap());
+    ///     }
+    /// });
+    /// ```
+    pub fn subscribe_channel(&self, key: &str) -> Receiver<ValueChange> {
         let (tx, rx) = channel();
-        let sources = self.sources.clone();
+        self.subscriptions
+            .add(key, Box::new(move |change| tx.send(change.clone()).is_ok()));
+        rx
+    }
 
-        // Create a watcher object in a separate thread
-        thread::spawn(move || {
-            let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |res| {
-                tx.send(res)
-                    .expect("Failed to send file change notification");
+    #[cfg(feature = "watch")]
+    /// Removes a subscription created by [`ConfigManager::subscribe`]. Returns `false` if it
+    /// didn't exist.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.subscriptions.remove(id)
+    }
+
+    #[cfg(feature = "watch")]
+    // internal function returning the paths of all file sources
+    fn file_sources(&self) -> Vec<FilePath> {
+        let mut files = self
+            .builder
+            .ordered_sources()
+            .into_iter()
+            .filter_map(|src| match src {
+                ConfigSource::File(fp) => Some(fp),
+                ConfigSource::Environment | ConfigSource::CommandLine(_) => None,
             })
-            .map_err(|e| ConfigError::FileWatchError(e.to_string()))
-            .unwrap();
-
-            if let Some(src) = sources.first() {
-                match src {
-                    ConfigSource::File(fp) => {
-                        println!("watching file: {}", fp);
-
-                        watcher
-                            .watch(Path::new(&fp.as_ref()), RecursiveMode::Recursive)
-                            .map_err(|e| ConfigError::FileWatchError(e.to_string()))
-                            .unwrap();
-                    }
-                    ConfigSource::Environment | ConfigSource::CommandLine(_) => unreachable!(),
-                }
+            .collect::<Vec<_>>();
+        for fp in &self.files {
+            if !files.contains(fp) {
+                files.push(fp.clone());
             }
-            // Block this thread until the shutdown signal is received
-            match term_rx.recv() {
-                Err(e) => println!("{:?}", e),
-                Ok(_) => {
-                    println!("File watcher shutting down.");
-                }
-            }
-        });
+        }
+        files
+    }
+
+    // internal function resolving a key, preferring an exact top-level match over a path
+    fn lookup(&self, key: &str) -> Option<&Value> {
+        match self.configs.get(key) {
+            Some(value) => Some(value),
+            None => KeyPath::parse(key).ok()?.get(&self.configs),
+        }
+    }
 
-        Ok(rx)
+    // internal function resolving a key mutably, see `lookup`
+    fn lookup_mut(&mut self, key: &str) -> Option<&mut Value> {
+        if self.configs.contains_key(key) {
+            return self.configs.get_mut(key);
+        }
+        KeyPath::parse(key).ok()?.get_mut(&mut self.configs)
     }
 
     fn convert_hashmap_to_value(hashmap: HashMap<String, Value>) -> Value {
@@ -0,0 +1,94 @@
+use serde_json::Value;
+
+use crate::ConfigMap;
+
+/// How arrays are combined when a later source sets an array that an earlier source already set.
+///
+/// Objects are always merged recursively; this only controls arrays.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::{ArrayMergeStrategy, ConfigManagerBuilder};
+///
+/// let mut builder = ConfigManagerBuilder::new();
+/// builder.array_merge_strategy(ArrayMergeStrategy::MergeByKey("name".to_string()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ArrayMergeStrategy {
+    /// The later array replaces the earlier one.
+    #[default]
+    Replace,
+    /// The later array's elements are appended to the earlier one.
+    Append,
+    /// Elements at the same index are merged, extra elements are appended.
+    MergeByIndex,
+    /// Object elements sharing the same value for the given field are merged, others are appended.
+    MergeByKey(String),
+}
+
+/// Merges `overlay` into `base`.
+///
+/// Objects are merged key by key, recursively. Arrays are combined according to `strategy`.
+/// Any other value in `overlay` replaces the value in `base`.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::merge::{merge_value, ArrayMergeStrategy};
+/// use serde_json::json;
+///
+/// let mut base = json!({"db": {"host": "localhost", "port": 5432}});
+/// merge_value(&mut base, json!({"db": {"port": 6432}}), &ArrayMergeStrategy::Replace);
+/// assert_eq!(base, json!({"db": {"host": "localhost", "port": 6432}}));
+/// ```
+pub fn merge_value(base: &mut Value, overlay: Value, strategy: &ArrayMergeStrategy) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                match base.get_mut(&k) {
+                    Some(existing) => merge_value(existing, v, strategy),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) => match strategy {
+            ArrayMergeStrategy::Replace => *base = overlay,
+            ArrayMergeStrategy::Append => base.extend(overlay),
+            ArrayMergeStrategy::MergeByIndex => {
+                for (i, v) in overlay.into_iter().enumerate() {
+                    match base.get_mut(i) {
+                        Some(existing) => merge_value(existing, v, strategy),
+                        None => base.push(v),
+                    }
+                }
+            }
+            ArrayMergeStrategy::MergeByKey(field) => {
+                for v in overlay {
+                    let existing = v
+                        .get(field)
+                        .and_then(|key| base.iter_mut().find(|item| item.get(field) == Some(key)));
+                    match existing {
+                        Some(existing) => merge_value(existing, v, strategy),
+                        None => base.push(v),
+                    }
+                }
+            }
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// Merges every top-level entry of `overlay` into `base`.
+pub(crate) fn merge_maps(base: &mut ConfigMap, overlay: ConfigMap, strategy: &ArrayMergeStrategy) {
+    for (k, v) in overlay {
+        match base.get_mut(&k) {
+            Some(existing) => merge_value(existing, v, strategy),
+            None => {
+                base.insert(k, v);
+            }
+        }
+    }
+}
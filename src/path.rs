@@ -0,0 +1,193 @@
+use std::fmt::{self, Display};
+
+use serde_json::Value;
+
+use crate::{error::ConfigError, ConfigMap};
+
+/// A single step of a [`KeyPath`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// An object key. When applied to an array, a numeric key is used as an index.
+    Key(String),
+    /// An array index.
+    Index(usize),
+}
+
+/// A path to a (possibly nested) configuration value.
+///
+/// Two syntaxes are accepted:
+///
+/// * dotted keys with optional array indices, e.g. `database.replicas[2].host`
+/// * JSON Pointer (RFC 6901), e.g. `/database/replicas/2/host`
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::path::{KeyPath, PathSegment};
+///
+/// let dotted = KeyPath::parse("database.replicas[2].host").unwrap();
+/// assert_eq!(
+///     dotted.segments(),
+///     &[
+///         PathSegment::Key("database".to_string()),
+///         PathSegment::Key("replicas".to_string()),
+///         PathSegment::Index(2),
+///         PathSegment::Key("host".to_string()),
+///     ]
+/// );
+/// assert_eq!(dotted.to_string(), "database.replicas[2].host");
+///
+/// let pointer = KeyPath::parse("/database/replicas/2/host").unwrap();
+/// assert_eq!(pointer.segments().len(), 4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyPath(Vec<PathSegment>);
+
+impl KeyPath {
+    /// Parses a dotted path or, if `key` starts with `/`, a JSON Pointer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::KeyNotFoundError`] if the path is malformed, e.g. `a..b` or `a[x]`.
+    pub fn parse(key: &str) -> Result<Self, ConfigError> {
+        if key.starts_with('/') {
+            Self::parse_pointer(key)
+        } else {
+            Self::parse_dotted(key)
+        }
+    }
+
+    /// Returns the segments of the path.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    /// Returns a reference to the value at this path.
+    pub fn get<'a>(&self, cfg_map: &'a ConfigMap) -> Option<&'a Value> {
+        let (first, rest) = self.0.split_first()?;
+        let PathSegment::Key(first) = first else {
+            return None;
+        };
+        rest.iter()
+            .try_fold(cfg_map.get(first)?, |value, segment| match segment {
+                PathSegment::Key(key) => match value {
+                    Value::Object(map) => map.get(key),
+                    Value::Array(arr) => arr.get(key.parse::<usize>().ok()?),
+                    _ => None,
+                },
+                PathSegment::Index(i) => value.as_array()?.get(*i),
+            })
+    }
+
+    /// Returns a mutable reference to the value at this path.
+    pub fn get_mut<'a>(&self, cfg_map: &'a mut ConfigMap) -> Option<&'a mut Value> {
+        let (first, rest) = self.0.split_first()?;
+        let PathSegment::Key(first) = first else {
+            return None;
+        };
+        rest.iter()
+            .try_fold(cfg_map.get_mut(first)?, |value, segment| match segment {
+                PathSegment::Key(key) => match value {
+                    Value::Object(map) => map.get_mut(key),
+                    Value::Array(arr) => arr.get_mut(key.parse::<usize>().ok()?),
+                    _ => None,
+                },
+                PathSegment::Index(i) => value.as_array_mut()?.get_mut(*i),
+            })
+    }
+
+    // Resolves numeric keys applied to arrays into indices, so that `/list/0` and `list[0]`
+    // produce the same canonical path.
+    pub(crate) fn normalized(&self, cfg_map: &ConfigMap) -> KeyPath {
+        let mut current: Option<&Value> = None;
+        let mut segments = Vec::with_capacity(self.0.len());
+        for (i, segment) in self.0.iter().enumerate() {
+            let segment = match (segment, current) {
+                (PathSegment::Key(key), Some(Value::Array(_))) => key
+                    .parse::<usize>()
+                    .map_or_else(|_| segment.clone(), PathSegment::Index),
+                _ => segment.clone(),
+            };
+            current = match (&segment, current) {
+                (PathSegment::Key(key), None) if i == 0 => cfg_map.get(key),
+                (PathSegment::Key(key), Some(value)) => value.get(key),
+                (PathSegment::Index(index), Some(value)) => value.get(index),
+                _ => None,
+            };
+            segments.push(segment);
+        }
+        KeyPath(segments)
+    }
+
+    // Returns the path itself followed by each of its ancestors, e.g. `a.b[0]`, `a.b`, `a`.
+    pub(crate) fn ancestors(&self) -> impl Iterator<Item = KeyPath> + '_ {
+        (1..=self.0.len())
+            .rev()
+            .map(|len| KeyPath(self.0[..len].to_vec()))
+    }
+
+    // Formats the path as a JSON Pointer which, unlike the dotted form, tells a key containing
+    // `.` apart from nested keys.
+    pub(crate) fn to_pointer(&self) -> String {
+        self.0
+            .iter()
+            .fold(String::new(), |pointer, segment| match segment {
+                PathSegment::Key(key) => pointer_join(&pointer, key),
+                PathSegment::Index(index) => pointer_join(&pointer, &index.to_string()),
+            })
+    }
+
+    fn parse_pointer(key: &str) -> Result<Self, ConfigError> {
+        let segments = key[1..]
+            .split('/')
+            .map(|token| PathSegment::Key(token.replace("~1", "/").replace("~0", "~")))
+            .collect();
+        Ok(Self(segments))
+    }
+
+    fn parse_dotted(key: &str) -> Result<Self, ConfigError> {
+        let invalid = || ConfigError::KeyNotFoundError(key.to_owned());
+        let mut segments = Vec::new();
+
+        for part in key.split('.') {
+            let (name, mut indices) = match part.find('[') {
+                Some(open) => (&part[..open], &part[open..]),
+                None => (part, ""),
+            };
+            if name.is_empty() && (segments.is_empty() || indices.is_empty()) {
+                return Err(invalid());
+            }
+            if !name.is_empty() {
+                segments.push(PathSegment::Key(name.to_string()));
+            }
+            while !indices.is_empty() {
+                let close = indices.find(']').ok_or_else(invalid)?;
+                let index = indices[1..close].parse::<usize>().map_err(|_| invalid())?;
+                segments.push(PathSegment::Index(index));
+                indices = &indices[close + 1..];
+                if !indices.is_empty() && !indices.starts_with('[') {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(Self(segments))
+    }
+}
+
+// Appends `key` to the JSON Pointer `parent`, escaping `~` and `/`.
+pub(crate) fn pointer_join(parent: &str, key: &str) -> String {
+    format!("{}/{}", parent, key.replace('~', "~0").replace('/', "~1"))
+}
+
+impl Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => f.write_str(key)?,
+                PathSegment::Key(key) => write!(f, ".{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{include::Layer, path::pointer_join, FilePath};
+
+// Reserved top-level key holding the profile sections of a file.
+const PROFILES_KEY: &str = "profiles";
+
+// Returns the sibling file holding the `profile` overrides of `path`, e.g. `config.prod.yaml`
+// for `config.yaml`.
+pub(crate) fn profile_path(path: &FilePath, profile: &str) -> FilePath {
+    let path = Path::new(path.as_ref());
+    let name = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => format!(
+            "{}.{}.{}",
+            stem.to_string_lossy(),
+            profile,
+            ext.to_string_lossy()
+        ),
+        _ => format!("{}.{}", path.to_string_lossy(), profile),
+    };
+    FilePath::new(path.with_file_name(name).to_string_lossy())
+}
+
+// Removes the profile sections from `layer`, returning the section of `profile` as a layer of
+// its own to be merged over it. A `profiles` key that isn't a map of sections is left alone.
+pub(crate) fn take_profile_section(layer: &mut Layer, profile: Option<&str>) -> Option<Layer> {
+    let Some(Value::Object(sections)) = layer.cfg_map.get_mut(PROFILES_KEY) else {
+        return None;
+    };
+    let section = profile.and_then(|profile| sections.remove(profile));
+    layer.cfg_map.remove(PROFILES_KEY);
+    let (Some(profile), Some(Value::Object(section))) = (profile, section) else {
+        return None;
+    };
+
+    let prefix = pointer_join(&pointer_join("", PROFILES_KEY), profile);
+    let locations = layer
+        .locations
+        .iter()
+        .filter_map(|(path, location)| {
+            let path = path.strip_prefix(&prefix)?;
+            path.starts_with('/').then(|| (path.to_string(), *location))
+        })
+        .collect();
+    Some(Layer {
+        source: layer.source.clone(),
+        cfg_map: section.into_iter().collect(),
+        locations,
+    })
+}
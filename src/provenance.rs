@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::{path::pointer_join as join, ConfigMap, ConfigSource};
+
+/// A position inside a configuration file, both 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A lower-priority value that was replaced while merging sources.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverriddenValue {
+    pub source: ConfigSource,
+    pub location: Option<Location>,
+    pub value: Value,
+}
+
+/// Where a configuration value came from.
+///
+/// Returned by [`ConfigManager::source_of`](crate::ConfigManager::source_of). Provenance is
+/// tracked for scalar values and arrays; for arrays combined with an
+/// [`ArrayMergeStrategy`](crate::ArrayMergeStrategy) other than `Replace`, `source` is the
+/// last source that contributed to the array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// The source that supplied the value.
+    pub source: ConfigSource,
+    /// The location of the key in the file, for file sources.
+    pub location: Option<Location>,
+    /// The value as supplied by `source`.
+    pub value: Value,
+    /// Values from lower-priority sources that were overridden, lowest priority first.
+    pub overridden: Vec<OverriddenValue>,
+}
+
+impl Provenance {
+    // Names the source that supplied the value, with its position for files.
+    pub(crate) fn describe_source(&self) -> String {
+        match (&self.source, self.location) {
+            (ConfigSource::File(path), Some(location)) => {
+                format!("{}:{}:{}", path, location.line, location.column)
+            }
+            (ConfigSource::File(path), None) => path.to_string(),
+            (ConfigSource::Environment, _) => "environment variables".to_string(),
+            (ConfigSource::CommandLine(_), _) => "command-line arguments".to_string(),
+        }
+    }
+}
+
+// Records, per value path, the source that supplied it while sources are merged. Paths are JSON
+// Pointers, so a key containing `.` doesn't collide with nested keys, and are ordered so that
+// the values below a path can be found with a range scan.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProvenanceTracker {
+    entries: BTreeMap<String, Provenance>,
+}
+
+impl ProvenanceTracker {
+    // Records the values of `cfg_map`, which is merged over everything recorded so far.
+    pub(crate) fn record(
+        &mut self,
+        source: &ConfigSource,
+        cfg_map: &ConfigMap,
+        locations: &HashMap<String, Location>,
+    ) {
+        let mut leaves = Vec::new();
+        for (k, v) in cfg_map {
+            flatten(join("", k), v, &mut leaves);
+        }
+
+        for (path, value) in leaves {
+            // A value replaces the previous one at the same path, as well as values below it
+            // (an object replaced by a scalar) or above it (a scalar replaced by an object).
+            let mut replaced = self.ancestors_and_descendants(&path);
+            if self.entries.contains_key(&path) {
+                replaced.push(path.clone());
+            }
+            replaced.sort();
+
+            let mut overridden = Vec::new();
+            for k in replaced {
+                let previous = self.entries.remove(&k).unwrap();
+                overridden.extend(previous.overridden);
+                overridden.push(OverriddenValue {
+                    source: previous.source,
+                    location: previous.location,
+                    value: previous.value,
+                });
+            }
+
+            let location = locations.get(&path).copied();
+            self.entries.insert(
+                path,
+                Provenance {
+                    source: source.clone(),
+                    location,
+                    value,
+                    overridden,
+                },
+            );
+        }
+    }
+
+    // Returns the recorded paths above and below `path`.
+    fn ancestors_and_descendants(&self, path: &str) -> Vec<String> {
+        let mut found = path
+            .match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .filter(|ancestor| self.entries.contains_key(*ancestor))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let start = format!("{}/", path);
+        found.extend(
+            self.entries
+                .range(start.clone()..)
+                .map(|(k, _)| k)
+                .take_while(|k| k.starts_with(&start))
+                .cloned(),
+        );
+        found
+    }
+
+    pub(crate) fn get(&self, path: &str) -> Option<&Provenance> {
+        self.entries.get(path)
+    }
+
+    // Returns the provenance of the value at `path` or, for elements of arrays, of the closest
+    // ancestor that was recorded.
+    pub(crate) fn get_nearest(&self, mut path: &str) -> Option<&Provenance> {
+        loop {
+            if let Some(provenance) = self.entries.get(path) {
+                return Some(provenance);
+            }
+            path = &path[..path.rfind('/')?];
+        }
+    }
+}
+
+fn flatten(path: String, value: &Value, leaves: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                flatten(join(&path, k), v, leaves);
+            }
+        }
+        _ => leaves.push((path, value.clone())),
+    }
+}
+
+// Locates mapping keys in YAML content. Keys inside sequences and flow collections
+// (`{a: 1}`, `[1, 2]`) aren't located, since provenance treats arrays as single values and
+// only block mappings are followed. Content with several documents isn't located at all.
+pub(crate) fn locate_yaml(content: &str) -> HashMap<String, Location> {
+    let mut locations = HashMap::new();
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    // Lines indented deeper than this belong to a value that was already located; the flag
+    // also skips sequence items at that indentation.
+    let mut skip: Option<(usize, bool)> = None;
+    // Whether a document has content, and whether it was ended by a marker.
+    let (mut has_content, mut ended) = (false, false);
+
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with("---") || trimmed.starts_with("...") {
+            ended = has_content;
+            continue;
+        }
+        if ended {
+            return HashMap::new();
+        }
+        has_content = true;
+        let indent = line.len() - trimmed.len();
+        let is_item = trimmed == "-" || trimmed.starts_with("- ");
+        let is_flow = trimmed.starts_with(['{', '[']);
+
+        if let Some((skip_indent, items)) = skip {
+            if indent > skip_indent || (items && is_item && indent == skip_indent) {
+                continue;
+            }
+            skip = None;
+        }
+        if is_item || is_flow {
+            skip = Some((indent, is_item));
+            continue;
+        }
+        let Some((key, rest)) = split_yaml_key(trimmed) else {
+            continue;
+        };
+
+        while parents.last().is_some_and(|(d, _)| *d >= indent) {
+            parents.pop();
+        }
+        let parent = parents.last().map_or("", |(_, parent)| parent.as_str());
+        let path = join(parent, &key);
+        locations.insert(
+            path.clone(),
+            Location {
+                line: i + 1,
+                column: indent + 1,
+            },
+        );
+
+        let rest = rest.trim();
+        if rest.is_empty()
+            || rest.starts_with('#')
+            || (rest.starts_with('&') && !rest.contains(' '))
+        {
+            parents.push((indent, path));
+        } else if rest.starts_with(['|', '>', '{', '[']) {
+            // Block scalars and flow collections may continue on deeper lines.
+            skip = Some((indent, false));
+        }
+    }
+    locations
+}
+
+fn split_yaml_key(line: &str) -> Option<(String, &str)> {
+    if let Some(quote) = line.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        let end = line[1..].find(quote)? + 1;
+        let rest = line[end + 1..].trim_start().strip_prefix(':')?;
+        return Some((line[1..end].to_string(), rest));
+    }
+    let (colon, _) = line.match_indices(':').find(|(i, _)| {
+        let after = &line[i + 1..];
+        after.is_empty() || after.starts_with([' ', '\t'])
+    })?;
+    Some((line[..colon].trim_end().to_string(), &line[colon + 1..]))
+}
+
+// Locates object keys in JSON content. Keys inside arrays aren't located, since provenance
+// treats arrays as single values.
+pub(crate) fn locate_json(content: &str) -> HashMap<String, Location> {
+    enum Frame {
+        Object { path: String, key: Option<String> },
+        Array,
+    }
+
+    let mut locations = HashMap::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let chars = content.chars().collect::<Vec<_>>();
+    let (mut i, mut line, mut column) = (0, 1, 1);
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                let start = Location { line, column };
+                let mut raw = String::from('"');
+                i += 1;
+                column += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        raw.push(chars[i]);
+                        i += 1;
+                        column += 1;
+                    }
+                    raw.push(chars[i]);
+                    i += 1;
+                    column += 1;
+                }
+                raw.push('"');
+
+                let next = chars[(i + 1).min(chars.len())..]
+                    .iter()
+                    .find(|c| !c.is_whitespace());
+                let in_array = stack.iter().any(|f| matches!(f, Frame::Array));
+                if let (Some(':'), Some(Frame::Object { path, key })) = (next, stack.last_mut()) {
+                    if let Ok(name) = serde_json::from_str::<String>(&raw) {
+                        if !in_array {
+                            locations.insert(join(path, &name), start);
+                        }
+                        *key = Some(name);
+                    }
+                }
+            }
+            '{' => {
+                let path = match stack.last() {
+                    Some(Frame::Object { path, key: Some(k) }) => join(path, k),
+                    _ => String::new(),
+                };
+                stack.push(Frame::Object { path, key: None });
+            }
+            '[' => stack.push(Frame::Array),
+            '}' | ']' => {
+                stack.pop();
+            }
+            _ => {}
+        }
+
+        if chars.get(i) == Some(&'\n') {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+        i += 1;
+    }
+    locations
+}
+
+// Locates keys in TOML content. Keys inside arrays of tables aren't located, since provenance
+// treats arrays as single values.
+#[cfg(feature = "toml")]
+pub(crate) fn locate_toml(content: &str) -> HashMap<String, Location> {
+    let mut locations = HashMap::new();
+    let mut table = String::new();
+    let mut in_array_table = false;
+    let mut multiline: Option<&str> = None;
+    let mut open_brackets = 0i32;
+
+    // Splits a dotted key on the dots outside of quotes, unquoting each part.
+    let split_key = |key: &str| {
+        let mut parts = vec![String::new()];
+        let mut quote = None;
+        for c in key.chars() {
+            match (c, quote) {
+                ('.', None) => parts.push(String::new()),
+                ('"' | '\'', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                (c, _) => parts.last_mut().unwrap().push(c),
+            }
+        }
+        parts
+            .into_iter()
+            .map(|part| part.trim().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    for (i, line) in content.lines().enumerate() {
+        if let Some(delimiter) = multiline {
+            if line.contains(delimiter) {
+                multiline = None;
+            }
+            continue;
+        }
+        if open_brackets > 0 {
+            open_brackets += line.matches('[').count() as i32 - line.matches(']').count() as i32;
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with("[[") {
+            in_array_table = true;
+            continue;
+        }
+        if let Some(header) = trimmed.strip_prefix('[') {
+            let name = header.split(']').next().unwrap_or_default();
+            table = split_key(name)
+                .iter()
+                .fold(String::new(), |parent, part| join(&parent, part));
+            in_array_table = false;
+            continue;
+        }
+        if in_array_table {
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once('=') else {
+            continue;
+        };
+        let path = split_key(key)
+            .iter()
+            .fold(table.clone(), |parent, part| join(&parent, part));
+        locations.insert(
+            path,
+            Location {
+                line: i + 1,
+                column: line.len() - trimmed.len() + 1,
+            },
+        );
+
+        let value = value.trim();
+        for delimiter in ["\"\"\"", "'''"] {
+            if value.matches(delimiter).count() == 1 {
+                multiline = Some(delimiter);
+            }
+        }
+        if value.starts_with('[') {
+            open_brackets = value.matches('[').count() as i32 - value.matches(']').count() as i32;
+        }
+    }
+    locations
+}
@@ -0,0 +1,90 @@
+use serde_json::Value;
+
+use crate::{error::ConfigError, file_reader::parse_file, path::KeyPath, ConfigManager, FilePath};
+
+// A JSON Schema (draft 2020-12) the merged configuration must match.
+#[derive(Debug, Clone)]
+pub(crate) enum Schema {
+    Inline(Value),
+    // Read on every build and reload, so edits to the schema are picked up with the config.
+    File(FilePath),
+}
+
+impl Schema {
+    // Returns the schema, reading it if it's a file.
+    pub(crate) fn load(&self) -> Result<Value, ConfigError> {
+        match self {
+            Schema::Inline(schema) => Ok(schema.clone()),
+            Schema::File(path) => read_schema(path),
+        }
+    }
+}
+
+// Checks the configuration of `cm` against `schema`, reporting every violation at once.
+pub(crate) fn validate(schema: &Value, cm: &ConfigManager) -> Result<(), ConfigError> {
+    let validator = jsonschema::draft202012::new(schema)
+        .map_err(|e| ConfigError::ValidationError(format!("Invalid schema: {}", e)))?;
+
+    let instance = cm.parse::<Value>()?;
+    let violations = validator
+        .iter_errors(&instance)
+        .map(|e| {
+            let pointer = e.instance_path().as_str();
+            let path = match KeyPath::parse(pointer) {
+                Ok(path) if !pointer.is_empty() => path.to_string(),
+                _ => "<root>".to_string(),
+            };
+            match cm.source_of(pointer) {
+                Some(provenance) => {
+                    format!("{}: {} (from {})", path, e, provenance.describe_source())
+                }
+                None => format!("{}: {}", path, e),
+            }
+        })
+        .collect::<Vec<_>>();
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(ConfigError::ValidationError(format!(
+        "Configuration does not match the schema:\n  {}",
+        violations.join("\n  ")
+    )))
+}
+
+fn read_schema(path: &FilePath) -> Result<Value, ConfigError> {
+    let content = std::fs::read_to_string(path.as_ref())
+        .map_err(|e| ConfigError::FileReadError(path.clone(), e.to_string()))?;
+    let (schema, _) = parse_file(path, &content)?;
+    Ok(Value::Object(schema.into_iter().collect()))
+}
+
+#[cfg(feature = "schemars")]
+/// Generates the JSON Schema (draft 2020-12) of the configurations [`ConfigManager::parse`]
+/// accepts as `T`, honouring its serde attributes such as `rename` and `default`.
+///
+/// The schema can be passed to [`ConfigManagerBuilder::schema`](crate::ConfigManagerBuilder::schema)
+/// or, with [`ConfigManagerBuilder::schema_for`](crate::ConfigManagerBuilder::schema_for),
+/// used to validate the configuration as it's built. Written to a file, it can validate
+/// configuration files ahead of time, or give editors autocompletion through a
+/// `# yaml-language-server: $schema=config.schema.json` comment.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::{schema::schema_for, Deserialize, JsonSchema};
+///
+/// #[derive(Deserialize, JsonSchema)]
+/// struct AppConfig {
+///     port: u16,
+///     #[serde(default)]
+///     debug: bool,
+/// }
+///
+/// let schema = schema_for::<AppConfig>();
+/// assert_eq!(schema["required"], serde_json::json!(["port"]));
+/// // Publish it as config.schema.json
+/// let json = serde_json::to_string_pretty(&schema).unwrap();
+/// ```
+pub fn schema_for<T: schemars::JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
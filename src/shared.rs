@@ -0,0 +1,93 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use arc_swap::ArcSwap;
+
+use crate::{error::ConfigError, ConfigManager};
+
+/// A thread-safe handle to a [`ConfigManager`] that can be reloaded while it is being read.
+///
+/// Cloning the handle is cheap, and every clone sees the same configuration. Readers take an
+/// immutable [`snapshot`](SharedConfig::snapshot) without locking; a snapshot stays consistent
+/// while reloads swap newer versions in behind it, so values read from one snapshot always come
+/// from the same version of the configuration.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::{ConfigManagerBuilder, ConfigSource, FilePath};
+///
+/// let mut builder = ConfigManagerBuilder::new();
+/// builder.add_source(ConfigSource::File(FilePath::new("test/test.json")));
+/// let shared = builder.build().unwrap().into_shared();
+///
+/// let worker = shared.clone();
+/// std::thread::spawn(move || {
+///     let config = worker.snapshot();
+///     println!("{:?}", config.get_i64("SOME_OBJ.TEST_KEY_INT"));
+/// })
+/// .join()
+/// .unwrap();
+///
+/// shared.reload().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedConfig(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    current: ArcSwap<ConfigManager>,
+    // Serializes reloads, so each one diffs against the version it replaces.
+    reloading: Mutex<()>,
+}
+
+impl SharedConfig {
+    /// Creates a handle sharing `config_manager`.
+    pub fn new(config_manager: ConfigManager) -> Self {
+        Self(Arc::new(Inner {
+            current: ArcSwap::from_pointee(config_manager),
+            reloading: Mutex::new(()),
+        }))
+    }
+
+    /// Returns the current version of the configuration.
+    ///
+    /// The snapshot is unaffected by later reloads; take a new one to observe them.
+    pub fn snapshot(&self) -> Arc<ConfigManager> {
+        self.0.current.load_full()
+    }
+
+    /// Re-reads and merges all sources, and swaps the result in as the current version.
+    ///
+    /// Subscribers registered with [`ConfigManager::subscribe`] are notified once the new
+    /// version is visible to readers, before another reload can start. Their callbacks must not
+    /// reload this configuration themselves, as that would deadlock.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] if the configuration can't be loaded or doesn't pass the validators, in
+    /// which case the current version is kept.
+    pub fn reload(&self) -> Result<(), ConfigError> {
+        let reloading = self
+            .0
+            .reloading
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let current = self.snapshot();
+        let next = current.reload()?;
+        #[cfg(feature = "watch")]
+        let changes = current.subscriptions.diff(&current, &next);
+        self.0.current.store(Arc::new(next));
+
+        // Still holding the reload lock, so change sets are delivered in the order they happened.
+        #[cfg(feature = "watch")]
+        current.subscriptions.dispatch(changes);
+        drop(reloading);
+        Ok(())
+    }
+}
+
+impl From<ConfigManager> for SharedConfig {
+    fn from(config_manager: ConfigManager) -> Self {
+        Self::new(config_manager)
+    }
+}
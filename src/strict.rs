@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{error::ConfigError, ConfigManager, ConfigSource};
+
+// The keys a configuration is expected to contain, as declared by a schema or a set of keys.
+#[derive(Debug, Clone, PartialEq)]
+// Only schemas describe scalars and arrays.
+#[cfg_attr(not(feature = "schema"), allow(dead_code))]
+pub(crate) enum Expected {
+    // Any key is accepted, e.g. in maps or values the schema doesn't describe.
+    Any,
+    // A value without keys, such as a string.
+    Scalar,
+    Object(BTreeMap<String, Expected>),
+    Array(Box<Expected>),
+}
+
+impl Expected {
+    // Builds the expected keys from dotted keys, such as `database.url`. The values under the
+    // keys may contain anything.
+    pub(crate) fn from_keys<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut expected = Expected::Object(BTreeMap::new());
+        for key in keys {
+            let mut current = &mut expected;
+            for segment in key.as_ref().split('.') {
+                if !matches!(current, Expected::Object(_)) {
+                    *current = Expected::Object(BTreeMap::new());
+                }
+                let Expected::Object(fields) = current else {
+                    unreachable!()
+                };
+                current = fields.entry(segment.to_string()).or_insert(Expected::Any);
+            }
+        }
+        expected
+    }
+
+    #[cfg(feature = "schema")]
+    // Builds the expected keys from the `properties` and `items` of a JSON Schema, following
+    // local `$ref`s and combining the alternatives of `allOf`, `anyOf` and `oneOf`.
+    pub(crate) fn from_schema(schema: &Value) -> Self {
+        Self::from_subschema(schema, schema, 0)
+    }
+
+    #[cfg(feature = "schema")]
+    fn from_subschema(schema: &Value, root: &Value, depth: usize) -> Self {
+        // Recursive types reference themselves; stop following them at some point.
+        const MAX_DEPTH: usize = 32;
+        let Value::Object(schema) = schema else {
+            return Expected::Any;
+        };
+        if depth > MAX_DEPTH {
+            return Expected::Any;
+        }
+
+        let mut expected = None;
+        let mut combine = |other: Expected| {
+            expected = Some(match expected.take() {
+                Some(current) => Expected::union(current, other),
+                None => other,
+            })
+        };
+        if let Some(target) = schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix('#'))
+            .and_then(|pointer| root.pointer(pointer))
+        {
+            combine(Self::from_subschema(target, root, depth + 1));
+        }
+        for key in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(alternatives)) = schema.get(key) {
+                let mut union = None;
+                for alternative in alternatives {
+                    let alternative = Self::from_subschema(alternative, root, depth + 1);
+                    union = Some(match union {
+                        Some(current) => Expected::union(current, alternative),
+                        None => alternative,
+                    });
+                }
+                combine(union.unwrap_or(Expected::Any));
+            }
+        }
+        if let Some(Value::Array(parts)) = schema.get("allOf") {
+            for part in parts {
+                combine(Self::from_subschema(part, root, depth + 1));
+            }
+        }
+
+        let allows_additional =
+            !matches!(schema.get("additionalProperties"), Some(Value::Bool(false)))
+                && !matches!(
+                    schema.get("unevaluatedProperties"),
+                    Some(Value::Bool(false))
+                );
+        if let Some(Value::Object(properties)) = schema.get("properties") {
+            if allows_additional && schema.contains_key("additionalProperties") {
+                combine(Expected::Any);
+            } else {
+                combine(Expected::Object(
+                    properties
+                        .iter()
+                        .map(|(k, v)| (k.clone(), Self::from_subschema(v, root, depth + 1)))
+                        .collect(),
+                ));
+            }
+        } else if !allows_additional {
+            combine(Expected::Object(BTreeMap::new()));
+        }
+        if let Some(items) = schema.get("items") {
+            combine(Expected::Array(Box::new(Self::from_subschema(
+                items,
+                root,
+                depth + 1,
+            ))));
+        }
+
+        expected.unwrap_or_else(|| match schema.get("type") {
+            Some(Value::String(t)) if t != "object" && t != "array" => Expected::Scalar,
+            Some(Value::Array(types))
+                if types
+                    .iter()
+                    .all(|t| t.as_str().is_some_and(|t| t != "object" && t != "array")) =>
+            {
+                Expected::Scalar
+            }
+            _ => Expected::Any,
+        })
+    }
+
+    // Combines two expectations, accepting the keys of either.
+    pub(crate) fn union(self, other: Expected) -> Expected {
+        match (self, other) {
+            (Expected::Scalar, other) | (other, Expected::Scalar) => other,
+            (Expected::Object(mut fields), Expected::Object(others)) => {
+                for (key, other) in others {
+                    let merged = match fields.remove(&key) {
+                        Some(existing) => existing.union(other),
+                        None => other,
+                    };
+                    fields.insert(key, merged);
+                }
+                Expected::Object(fields)
+            }
+            (Expected::Array(items), Expected::Array(others)) => {
+                Expected::Array(Box::new(items.union(*others)))
+            }
+            _ => Expected::Any,
+        }
+    }
+
+    // Collects the paths of the keys of `value`, found at `path`, that aren't expected, along
+    // with the expected key that's closest to each one.
+    fn unknown(&self, path: &str, value: &Value, unknown: &mut Vec<(String, Option<String>)>) {
+        match (self, value) {
+            (Expected::Object(fields), Value::Object(map)) => {
+                for (key, value) in map {
+                    let child = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    match fields.get(key) {
+                        Some(expected) => expected.unknown(&child, value, unknown),
+                        None => unknown.push((child, suggest(key, fields.keys()))),
+                    }
+                }
+            }
+            (Expected::Array(items), Value::Array(values)) => {
+                for (i, value) in values.iter().enumerate() {
+                    items.unknown(&format!("{}[{}]", path, i), value, unknown);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Returns the candidate closest to `key`, if any is close enough to be a likely typo.
+fn suggest<'a, I>(key: &str, candidates: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let max_distance = (key.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::damerau_levenshtein(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.clone())
+}
+
+// Rejects the keys of `cm` that `expected` doesn't declare, reporting each one with the source
+// that supplied it. Keys from the environment are let through, as it's shared with other
+// programs.
+pub(crate) fn check_unknown_keys(
+    cm: &ConfigManager,
+    expected: &Expected,
+) -> Result<(), ConfigError> {
+    let configs = cm.parse::<Value>()?;
+    let mut unknown = Vec::new();
+    expected.unknown("", &configs, &mut unknown);
+
+    let mut reports = Vec::new();
+    for (path, suggestion) in unknown {
+        let provenance = cm.source_of(&path);
+        if provenance.is_some_and(|p| p.source == ConfigSource::Environment) {
+            continue;
+        }
+        let mut report = path;
+        if let Some(provenance) = provenance {
+            report.push_str(&format!(" (from {})", provenance.describe_source()));
+        }
+        if let Some(suggestion) = suggestion {
+            report.push_str(&format!(", did you mean `{}`?", suggestion));
+        }
+        reports.push(report);
+    }
+    if reports.is_empty() {
+        return Ok(());
+    }
+    reports.sort();
+    Err(ConfigError::ValidationError(format!(
+        "Unknown configuration keys:\n  {}",
+        reports.join("\n  ")
+    )))
+}
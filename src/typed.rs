@@ -0,0 +1,261 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{error::ConfigError, ConfigManager, ConfigManagerBuilder};
+
+/// A configuration struct whose keys, defaults, sources and constraints are declared on its
+/// fields, usually through `#[derive(Config)]`.
+///
+/// The derive reads `#[config(...)]` attributes. On the struct:
+///
+/// * `file = "config.yaml"` - a file source, may be repeated
+/// * `env_prefix = "APP_"` - the prefix of the environment variables read, see
+///   [`Config::builder`]
+/// * `strict` - rejects keys that aren't fields, see
+///   [`ConfigManagerBuilder::strict`](crate::ConfigManagerBuilder::strict)
+///
+/// On its fields:
+///
+/// * `default = 30` - the value used when no source sets the field, any serializable expression
+/// * `env = "DATABASE_URL"` - an environment variable read into the field
+/// * `cli = "port"` - a command-line flag read into the field
+/// * `doc = "..."` - a description of the field, defaulting to its doc comment
+/// * `secret` - keeps the value out of error messages and marks it in [`Config::fields`]
+/// * `range(min = 1, max = 65535)` - bounds the value, either bound may be left out
+/// * `regex = "^[a-z]+$"` - requires the value to match the pattern, which is checked at compile
+///   time
+/// * `non_empty` - rejects empty strings, collections and `None`
+/// * `nested` - the field is itself a `Config` struct, whose keys are nested under the field
+///
+/// Keys are the field names, renamed by `#[serde(rename = "...")]` and the struct's
+/// `#[serde(rename_all = "...")]` as serde does. Constraints on an `Option` field apply to the
+/// value inside it, if any.
+///
+/// `#[serde(alias)]` and `#[serde(flatten)]` change the keys a struct accepts in ways the
+/// derive can't follow, and are rejected:
+///
+/// ```compile_fail
+/// use rustic_config::{Config, Deserialize};
+///
+/// #[derive(Config, Deserialize)]
+/// struct AppConfig {
+///     #[serde(alias = "listen_port")]
+///     port: u16,
+/// }
+/// ```
+///
+/// Likewise, an invalid `regex` pattern is an error when the struct is compiled:
+///
+/// ```compile_fail
+/// use rustic_config::{Config, Deserialize};
+///
+/// #[derive(Config, Deserialize)]
+/// struct AppConfig {
+///     #[config(regex = "[a-z")]
+///     name: String,
+/// }
+/// ```
+///
+/// Flags of `bool` fields are set by `--flag` and `--no-flag`. Other fields also take their
+/// value from the next argument, as in `--port 8080`.
+///
+/// # Examples
+///
+/// ```
+/// use rustic_config::{Config, Deserialize};
+///
+/// #[derive(Config, Deserialize)]
+/// #[config(env_prefix = "MYAPP_")]
+/// struct AppConfig {
+///     /// Port the server listens on.
+///     #[config(default = 8080, cli = "port", range(min = 1))]
+///     port: u16,
+///     #[config(env = "DATABASE_URL", secret, regex = "^postgres://")]
+///     database_url: Option<String>,
+/// }
+///
+/// let config = AppConfig::load().unwrap();
+/// assert_eq!(config.port, 8080);
+/// ```
+pub trait Config: DeserializeOwned {
+    /// Registers the keys, defaults, environment variables and flags of the fields, with their
+    /// keys nested under `prefix`.
+    fn configure(builder: &mut ConfigManagerBuilder, prefix: &str);
+
+    /// Describes the fields, with their keys nested under `prefix`.
+    fn fields(prefix: &str) -> Vec<ConfigField>;
+
+    /// Appends the constraint violations of the fields to `violations`, with their keys nested
+    /// under `prefix`.
+    #[doc(hidden)]
+    fn check(&self, prefix: &str, violations: &mut Vec<String>);
+
+    /// Returns a builder reading the declared files, then the environment, then the process
+    /// arguments, configured for `Self` and validating every build and reload against it.
+    ///
+    /// With an `env_prefix`, every variable starting with the prefix is read, along with those
+    /// named by the `env` attributes of the fields. Without one, only the variables named by
+    /// `env` attributes are read, so unrelated variables such as `PATH` are left out.
+    fn builder() -> ConfigManagerBuilder;
+
+    /// Checks the constraints declared on the fields, reporting every violation at once.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::ValidationError`] listing each violation with its key.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut violations = Vec::new();
+        self.check("", &mut violations);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(ConfigError::ValidationError(format!(
+            "Invalid configuration:\n  {}",
+            violations.join("\n  ")
+        )))
+    }
+
+    /// Parses and validates `Self` from a built configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] if the configuration doesn't parse into `Self` or violates its
+    /// constraints.
+    fn from_manager(config_manager: &ConfigManager) -> Result<Self, ConfigError> {
+        let config = config_manager.parse::<Self>()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads `Self` from the sources of [`Config::builder`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] if a source can't be read, or the configuration doesn't parse into
+    /// `Self` or violates its constraints.
+    fn load() -> Result<Self, ConfigError> {
+        Self::from_manager(&Self::builder().build()?)
+    }
+}
+
+/// The description of a field of a [`Config`] struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigField {
+    /// The dotted key of the field.
+    pub key: String,
+    /// The description of the field.
+    pub doc: Option<String>,
+    /// The environment variable read into the field, besides the prefixed one.
+    pub env: Option<String>,
+    /// The command-line flag read into the field, besides `--key`.
+    pub cli: Option<String>,
+    /// The value used when no source sets the field.
+    pub default: Option<Value>,
+    /// Whether the value must be kept out of logs and error messages.
+    pub secret: bool,
+}
+
+// Support code for `#[derive(Config)]`, not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        fmt::Display,
+        sync::OnceLock,
+    };
+
+    pub use regex::Regex;
+    pub use serde_json::to_value;
+
+    // Values that `non_empty` applies to.
+    pub trait IsEmpty {
+        fn is_empty_value(&self) -> bool;
+    }
+
+    impl IsEmpty for String {
+        fn is_empty_value(&self) -> bool {
+            self.trim().is_empty()
+        }
+    }
+
+    impl<T> IsEmpty for Vec<T> {
+        fn is_empty_value(&self) -> bool {
+            self.is_empty()
+        }
+    }
+
+    impl<K, V, S> IsEmpty for HashMap<K, V, S> {
+        fn is_empty_value(&self) -> bool {
+            self.is_empty()
+        }
+    }
+
+    impl<K, V> IsEmpty for BTreeMap<K, V> {
+        fn is_empty_value(&self) -> bool {
+            self.is_empty()
+        }
+    }
+
+    impl<T: IsEmpty> IsEmpty for Option<T> {
+        fn is_empty_value(&self) -> bool {
+            self.as_ref().is_none_or(T::is_empty_value)
+        }
+    }
+
+    pub fn key(prefix: &str, key: &str) -> String {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    }
+
+    pub fn check_non_empty<T: IsEmpty>(value: &T) -> Option<String> {
+        value
+            .is_empty_value()
+            .then(|| "must not be empty".to_string())
+    }
+
+    pub fn check_range<T: PartialOrd + Display>(
+        value: &T,
+        min: Option<T>,
+        max: Option<T>,
+        secret: bool,
+    ) -> Option<String> {
+        let message = match (&min, &max) {
+            (Some(min), Some(max)) if value < min || value > max => {
+                format!("must be between {} and {}", min, max)
+            }
+            (Some(min), _) if value < min => format!("must be at least {}", min),
+            (_, Some(max)) if value > max => format!("must be at most {}", max),
+            _ => return None,
+        };
+        Some(got(message, value, secret))
+    }
+
+    // `cache` holds the compiled `pattern`, which the derive has already checked to be valid.
+    pub fn check_regex<T: AsRef<str>>(
+        value: &T,
+        cache: &'static OnceLock<Regex>,
+        pattern: &str,
+        secret: bool,
+    ) -> Option<String> {
+        let regex = cache.get_or_init(|| Regex::new(pattern).expect("invalid regex"));
+        if regex.is_match(value.as_ref()) {
+            return None;
+        }
+        Some(got(
+            format!("must match `{}`", pattern),
+            format_args!("{:?}", value.as_ref()),
+            secret,
+        ))
+    }
+
+    fn got(message: String, value: impl Display, secret: bool) -> String {
+        if secret {
+            message
+        } else {
+            format!("{}, got {}", message, value)
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use notify::{
+    Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Result as NotifyResult,
+    Watcher,
+};
+
+use serde_json::Value;
+
+use crate::{error::ConfigError, ConfigManager, FilePath};
+
+/// A change to one or more watched configuration files.
+#[derive(Debug, Clone)]
+pub struct FileChangeEvent {
+    /// The file sources affected by the change.
+    pub paths: Vec<FilePath>,
+    /// The underlying file system notifications.
+    pub events: Vec<Event>,
+}
+
+impl FileChangeEvent {
+    // Whether the change may have modified the content of a watched file. Access events are
+    // ignored, since reloading reads the files and would otherwise trigger itself.
+    pub(crate) fn is_content_change(&self) -> bool {
+        self.events.iter().any(|event| {
+            matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) | EventKind::Any
+            )
+        })
+    }
+
+    // Folds `other` into this change, keeping each affected file once.
+    fn coalesce(&mut self, other: FileChangeEvent) {
+        for path in other.paths {
+            if !self.paths.contains(&path) {
+                self.paths.push(path);
+            }
+        }
+        self.events.extend(other.events);
+    }
+}
+
+// Options controlling how file sources are watched, set on the `ConfigManagerBuilder`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WatchOptions {
+    pub(crate) debounce: Option<Duration>,
+    pub(crate) poll_interval: Option<Duration>,
+}
+
+// The polling interval used when falling back from a native watcher that failed to initialise.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// How often the watcher thread checks for the shutdown signal while idle.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Starts watching every file in `files` and spawns a thread handing each notification, tagged
+// with the files it concerns, to `on_event` until the shutdown signal is received on `term_rx`
+// or `on_event` returns `false`. With a debounce window, notifications are held back until no
+// new one arrived for the whole window and then handed over as a single change.
+//
+// Setting up the watcher happens before returning, so its errors are returned here; errors
+// occurring while watching are handed to `on_event`.
+pub(crate) fn spawn_watcher<F>(
+    files: Vec<FilePath>,
+    options: WatchOptions,
+    term_rx: oneshot::Receiver<()>,
+    mut on_event: F,
+) -> Result<WatchHandle, ConfigError>
+where
+    F: FnMut(NotifyResult<FileChangeEvent>) -> bool + Send + 'static,
+{
+    let mut watched = WatchSet::new(files);
+    for file in &watched.files {
+        std::fs::metadata(&file.path)
+            .map_err(|e| ConfigError::FileWatchError(format!("{}: {}", file.path.display(), e)))?;
+    }
+    let (raw_tx, raw_rx) = channel();
+    let mut watcher = start_watcher(&options, &mut watched, raw_tx)
+        .map_err(|e| ConfigError::FileWatchError(e.to_string()))?;
+
+    let thread = thread::spawn(move || {
+        let mut pending: Option<(FileChangeEvent, Instant)> = None;
+        // Run until the shutdown signal is received
+        while let Err(oneshot::TryRecvError::Empty) = term_rx.try_recv() {
+            let timeout = pending
+                .as_ref()
+                .map(|(_, deadline)| deadline.saturating_duration_since(Instant::now()))
+                .map_or(SHUTDOWN_POLL_INTERVAL, |left| {
+                    left.min(SHUTDOWN_POLL_INTERVAL)
+                });
+
+            let mut outgoing = Vec::new();
+            match raw_rx.recv_timeout(timeout) {
+                Ok(Ok(event)) => {
+                    let paths = watched.tag(&event);
+                    if let Err(e) = watched.rearm(watcher.as_mut()) {
+                        outgoing.push(Err(e));
+                    }
+                    if !paths.is_empty() {
+                        let change = FileChangeEvent {
+                            paths,
+                            events: vec![event],
+                        };
+                        match (options.debounce, pending.as_mut()) {
+                            (None, _) => outgoing.push(Ok(change)),
+                            (Some(window), Some((pending, deadline))) => {
+                                pending.coalesce(change);
+                                *deadline = Instant::now() + window;
+                            }
+                            (Some(window), None) => {
+                                pending = Some((change, Instant::now() + window))
+                            }
+                        }
+                    }
+                }
+                Ok(Err(e)) => outgoing.push(Err(e)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if pending
+                .as_ref()
+                .is_some_and(|(_, deadline)| *deadline <= Instant::now())
+            {
+                outgoing.push(Ok(pending.take().unwrap().0));
+            }
+            if !outgoing.into_iter().all(&mut on_event) {
+                break;
+            }
+        }
+    });
+    Ok(WatchHandle { thread })
+}
+
+/// The outcomes of the reloads performed by [`ConfigManager::watch_and_reload`].
+pub type ReloadReceiver = Receiver<Result<(), ConfigError>>;
+
+/// A handle to the thread watching the configuration files, returned by
+/// [`ConfigManager::watch_file_changes`] and [`ConfigManager::watch_and_reload`].
+///
+/// The thread stops once the terminator passed to `watch_file_changes` or `watch_and_reload`
+/// is sent or dropped. The thread of `watch_file_changes` also stops once the receiver of the
+/// change events is dropped, while `watch_and_reload` keeps reloading without a receiver.
+#[derive(Debug)]
+pub struct WatchHandle {
+    thread: thread::JoinHandle<()>,
+}
+
+impl WatchHandle {
+    /// Returns `true` once the watcher thread has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the watcher thread to stop.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::FileWatchError`] if the thread panicked.
+    pub fn join(self) -> Result<(), ConfigError> {
+        self.thread
+            .join()
+            .map_err(|_| ConfigError::FileWatchError("Watcher thread panicked".to_string()))
+    }
+}
+
+// Creates a watcher and starts watching the files. The native watcher is used unless polling
+// was requested; if it fails to initialise or to watch the files (e.g. when inotify instances
+// are exhausted), polling is used instead. Polling compares file contents rather than
+// modification times, which network and overlay filesystems don't always update.
+fn start_watcher(
+    options: &WatchOptions,
+    watched: &mut WatchSet,
+    tx: Sender<NotifyResult<Event>>,
+) -> NotifyResult<Box<dyn Watcher + Send>> {
+    if options.poll_interval.is_none() {
+        let native = RecommendedWatcher::new(tx.clone(), notify::Config::default());
+        if let Ok(mut watcher) = native {
+            if watched.rearm(&mut watcher).is_ok() {
+                return Ok(Box::new(watcher));
+            }
+            watched.dirs.clear();
+        }
+    }
+
+    let config = notify::Config::default()
+        .with_poll_interval(options.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL))
+        .with_compare_contents(true);
+    let mut watcher = PollWatcher::new(tx, config)?;
+    watched.rearm(&mut watcher)?;
+    Ok(Box::new(watcher))
+}
+
+// A watched file source. `path` is absolute but keeps its file name unresolved, so a symlinked
+// file (like the entries of a mounted Kubernetes ConfigMap) is watched as the link itself;
+// `target` is the file it currently resolves to.
+struct WatchedFile {
+    source: FilePath,
+    path: PathBuf,
+    target: Option<PathBuf>,
+}
+
+// The watched files and the directories watched for them. Files are watched through their
+// parent directory and the directory of their resolved target, so that atomic replacements
+// (renames and symlink swaps) are noticed and the watch survives them.
+struct WatchSet {
+    files: Vec<WatchedFile>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl WatchSet {
+    fn new(files: Vec<FilePath>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|source| {
+                let path = absolute(Path::new(source.as_ref()));
+                let target = std::fs::canonicalize(&path).ok();
+                WatchedFile {
+                    source,
+                    path,
+                    target,
+                }
+            })
+            .collect();
+        Self {
+            files,
+            dirs: HashSet::new(),
+        }
+    }
+
+    // Returns the watched files an event concerns: files the event names, either directly or
+    // through their resolved target, and files whose target changed (e.g. a `..data` symlink
+    // swap). Events without paths (e.g. a rescan request) concern every watched file.
+    fn tag(&mut self, event: &Event) -> Vec<FilePath> {
+        self.files
+            .iter_mut()
+            .filter_map(|file| {
+                let target = std::fs::canonicalize(&file.path).ok();
+                let named = event.paths.is_empty()
+                    || event.paths.iter().any(|path| {
+                        *path == file.path
+                            || Some(path) == file.target.as_ref()
+                            || Some(path) == target.as_ref()
+                    });
+                let swapped = target != file.target;
+                file.target = target;
+                (named || swapped).then(|| file.source.clone())
+            })
+            .collect()
+    }
+
+    // Watches the directories the files currently need and stops watching the ones they no
+    // longer do, such as the data directory a swapped symlink pointed to.
+    fn rearm(&mut self, watcher: &mut dyn Watcher) -> NotifyResult<()> {
+        let wanted = self
+            .files
+            .iter()
+            .flat_map(|file| [Some(&file.path), file.target.as_ref()])
+            .flatten()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect::<HashSet<_>>();
+
+        for dir in self.dirs.difference(&wanted) {
+            // The directory may already be gone, which removed its watch.
+            let _ = watcher.unwatch(dir);
+        }
+        self.dirs.retain(|dir| wanted.contains(dir));
+
+        let mut result = Ok(());
+        for dir in wanted {
+            if self.dirs.contains(&dir) {
+                continue;
+            }
+            match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.dirs.insert(dir);
+                }
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
+    }
+}
+
+// Makes `path` absolute, resolving symlinks in every component but the file name.
+fn absolute(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => std::fs::canonicalize(parent)
+            .map(|parent| parent.join(name))
+            .unwrap_or(path),
+        _ => path,
+    }
+}
+
+#[cfg(feature = "async")]
+/// A stream of reload outcomes, returned by [`ConfigManager::watch_and_reload_stream`].
+///
+/// Yields `Ok(())` after every successful reload and the error of every failed one. Dropping
+/// the stream stops watching.
+#[derive(Debug)]
+pub struct ReloadStream {
+    rx: tokio::sync::mpsc::UnboundedReceiver<Result<(), ConfigError>>,
+    // Dropping the sender disconnects the watcher thread's shutdown signal.
+    _term_tx: oneshot::Sender<()>,
+    handle: WatchHandle,
+}
+
+#[cfg(feature = "async")]
+impl ReloadStream {
+    pub(crate) fn new(
+        rx: tokio::sync::mpsc::UnboundedReceiver<Result<(), ConfigError>>,
+        term_tx: oneshot::Sender<()>,
+        handle: WatchHandle,
+    ) -> Self {
+        Self {
+            rx,
+            _term_tx: term_tx,
+            handle,
+        }
+    }
+
+    /// Stops watching, returning the handle of the watcher thread to wait for it to stop.
+    pub fn stop(self) -> WatchHandle {
+        self.handle
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for ReloadStream {
+    type Item = Result<(), ConfigError>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// A change of the value at a subscribed key, observed when the configuration was reloaded.
+///
+/// A key that doesn't exist is reported as [`Value::Null`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueChange {
+    /// The key or [`KeyPath`](crate::path::KeyPath) that was subscribed to.
+    pub key: String,
+    /// The value before the reload.
+    pub old: Value,
+    /// The value after the reload.
+    pub new: Value,
+}
+
+/// Identifies a subscription created by [`ConfigManager::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+// Returns `false` once the subscriber is gone and the subscription should be dropped.
+type Callback = Box<dyn FnMut(&ValueChange) -> bool + Send>;
+
+struct Subscriber {
+    key: String,
+    callback: Mutex<Callback>,
+    // Set on removal, so a dispatch already under way skips the callback from then on.
+    removed: AtomicBool,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    entries: Vec<(SubscriptionId, Arc<Subscriber>)>,
+}
+
+// Per-key change subscriptions, shared by every configuration reloaded from the same manager.
+#[derive(Clone, Default)]
+pub(crate) struct Subscriptions(Arc<Mutex<Registry>>);
+
+impl Subscriptions {
+    pub(crate) fn add(&self, key: &str, callback: Callback) -> SubscriptionId {
+        let mut registry = self.lock();
+        let id = SubscriptionId(registry.next_id);
+        registry.next_id += 1;
+        let subscriber = Subscriber {
+            key: key.to_string(),
+            callback: Mutex::new(callback),
+            removed: AtomicBool::new(false),
+        };
+        registry.entries.push((id, Arc::new(subscriber)));
+        id
+    }
+
+    pub(crate) fn remove(&self, id: SubscriptionId) -> bool {
+        let mut registry = self.lock();
+        let Some(index) = registry
+            .entries
+            .iter()
+            .position(|(existing, _)| *existing == id)
+        else {
+            return false;
+        };
+        let (_, subscriber) = registry.entries.remove(index);
+        subscriber.removed.store(true, Ordering::SeqCst);
+        true
+    }
+
+    // Returns the changes between `old` and `new` for every subscribed key whose value differs.
+    pub(crate) fn diff(
+        &self,
+        old: &ConfigManager,
+        new: &ConfigManager,
+    ) -> Vec<(SubscriptionId, ValueChange)> {
+        let value = |cm: &ConfigManager, key: &str| cm.try_get(key).cloned().unwrap_or(Value::Null);
+        self.lock()
+            .entries
+            .iter()
+            .filter_map(|(id, subscriber)| {
+                let key = &subscriber.key;
+                let (old, new) = (value(old, key), value(new, key));
+                (old != new).then(|| {
+                    let key = key.clone();
+                    (*id, ValueChange { key, old, new })
+                })
+            })
+            .collect()
+    }
+
+    // Hands each change to its subscriber. Callbacks run without holding the registry lock, so
+    // they may subscribe, or unsubscribe themselves or others; a removed subscriber isn't called
+    // again, even by this dispatch.
+    pub(crate) fn dispatch(&self, changes: Vec<(SubscriptionId, ValueChange)>) {
+        if changes.is_empty() {
+            return;
+        }
+        let subscribers = {
+            let registry = self.lock();
+            changes
+                .into_iter()
+                .filter_map(|(id, change)| {
+                    let (_, subscriber) = registry
+                        .entries
+                        .iter()
+                        .find(|(existing, _)| *existing == id)?;
+                    Some((id, Arc::clone(subscriber), change))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (id, subscriber, change) in subscribers {
+            let mut callback = subscriber
+                .callback
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if subscriber.removed.load(Ordering::SeqCst) {
+                continue;
+            }
+            if !callback(&change) {
+                drop(callback);
+                self.remove(id);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for Subscriptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscriptions")
+            .field("len", &self.lock().entries.len())
+            .finish()
+    }
+}